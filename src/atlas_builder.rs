@@ -0,0 +1,392 @@
+use gl;
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::*;
+
+use super::atlas_manifest::*;
+use super::atlas_packer::*;
+use super::texture::*;
+use super::texture_format::*;
+
+#[derive(Debug)]
+pub enum AtlasBuilderError {
+    /// Two images share a name
+    DuplicateName,
+    /// An image couldn't be read, decoded or placed
+    Texture(Texture2DError),
+}
+
+/// Options controlling how images are packed into atlas pages
+#[derive(Debug, Clone, Copy)]
+pub struct AtlasBuilderOptions {
+    /// The largest width and height of a page
+    pub max_size: u32,
+    /// The number of pixels surrounding every image
+    pub padding: u32,
+    /// Fills the padding with the edge pixels of each image instead of leaving it transparent
+    pub extrude: bool,
+    /// Removes fully transparent rows and columns from the border of each image
+    pub trim: bool,
+}
+
+impl Default for AtlasBuilderOptions {
+    fn default() -> Self {
+        Self {
+            max_size: 2048,
+            padding: 1,
+            extrude: false,
+            trim: true,
+        }
+    }
+}
+
+/// A tightly packed RGBA8 atlas image
+#[derive(Debug, Clone)]
+pub struct AtlasPage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl AtlasPage {
+    pub fn save_png(&self, path: &Path) -> Result<(), Texture2DError> {
+        match write_png(path, self.width, self.height, gl::RGBA, &self.data) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(Texture2DError::CouldNotWriteFile),
+        }
+    }
+}
+
+/// Packs every png in a directory, naming each image after its file stem
+///
+/// Pages are named `{page_name}_{index}.png` in the manifest. Doesn't require a GL context.
+/// Files sharing a stem, ie. `a.png` and `a.PNG`, return `AtlasBuilderError::DuplicateName`.
+pub fn pack_directory(
+    path: &Path,
+    page_name: &str,
+    options: AtlasBuilderOptions,
+) -> Result<(Vec<AtlasPage>, AtlasManifest), AtlasBuilderError> {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return Err(AtlasBuilderError::Texture(Texture2DError::FileNotFound)),
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| match path.extension().and_then(OsStr::to_str) {
+            Some(extension) => extension.eq_ignore_ascii_case("png"),
+            None => false,
+        })
+        .collect();
+    paths.sort();
+
+    // decode to 8 bit RGBA without touching the color space
+    let load_options = LoadOptions {
+        strip_16: true,
+        color_space: Some(ColorSpace::Linear),
+        ..LoadOptions::default()
+    };
+    let mut images = Vec::with_capacity(paths.len());
+    for path in paths.iter() {
+        let image = match decode_file(path, load_options) {
+            Ok(image) => image,
+            Err(e) => return Err(AtlasBuilderError::Texture(e)),
+        };
+        let name = match path.file_stem().and_then(OsStr::to_str) {
+            Some(name) => name.to_string(),
+            None => return Err(AtlasBuilderError::Texture(Texture2DError::FileNotFound)),
+        };
+        let data = match image.format {
+            TextureFormat::R8 => image
+                .data
+                .iter()
+                .flat_map(|&l| vec![l, l, l, 255])
+                .collect(),
+            TextureFormat::RG8 => image
+                .data
+                .chunks(2)
+                .flat_map(|la| vec![la[0], la[0], la[0], la[1]])
+                .collect(),
+            TextureFormat::RGB8 => image
+                .data
+                .chunks(3)
+                .flat_map(|rgb| vec![rgb[0], rgb[1], rgb[2], 255])
+                .collect(),
+            TextureFormat::RGBA8 => image.data,
+            _ => {
+                return Err(AtlasBuilderError::Texture(
+                    Texture2DError::FormatNotSupported,
+                ))
+            }
+        };
+        images.push((name, image.width, image.height, data));
+    }
+
+    pack_images(images, page_name, options)
+}
+
+/// Packs named, tightly packed RGBA8 images into as few pages as possible
+///
+/// Every image needs a unique name, duplicates return `AtlasBuilderError::DuplicateName`.
+pub fn pack_images(
+    images: Vec<(String, u32, u32, Vec<u8>)>,
+    page_name: &str,
+    options: AtlasBuilderOptions,
+) -> Result<(Vec<AtlasPage>, AtlasManifest), AtlasBuilderError> {
+    let mut names = HashSet::with_capacity(images.len());
+    if !images.iter().all(|image| names.insert(&image.0)) {
+        return Err(AtlasBuilderError::DuplicateName);
+    }
+
+    // trim every image
+    let mut trimmed = Vec::with_capacity(images.len());
+    for (name, width, height, data) in images {
+        if packed_size(4, width, height, 1) != Some(data.len()) {
+            return Err(AtlasBuilderError::Texture(
+                Texture2DError::InvalidDataDimensions,
+            ));
+        }
+        let rect = if options.trim {
+            opaque_bounds(width, height, &data)
+        } else {
+            AtlasRect {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            }
+        };
+        let mut cropped = Vec::with_capacity((rect.width * rect.height * 4) as usize);
+        for y in rect.y..rect.y + rect.height {
+            let start = ((y * width + rect.x) * 4) as usize;
+            cropped.extend_from_slice(&data[start..start + (rect.width * 4) as usize]);
+        }
+        trimmed.push((name, width, height, rect, cropped));
+    }
+
+    // pack the tallest images first so shelves fill up evenly
+    let mut order: Vec<usize> = (0..trimmed.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (&trimmed[a].3, &trimmed[b].3);
+        (b.height, b.width).cmp(&(a.height, a.width))
+    });
+
+    let padding = options.padding;
+    let mut packers: Vec<ShelfPacker> = Vec::new();
+    let mut placements = vec![
+        (
+            0,
+            AtlasRect {
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 0
+            }
+        );
+        trimmed.len()
+    ];
+    for &index in order.iter() {
+        let rect = trimmed[index].3;
+        let (padded_width, padded_height) = (rect.width + 2 * padding, rect.height + 2 * padding);
+        let mut placement = None;
+        for (page, packer) in packers.iter_mut().enumerate() {
+            if let Some(padded) = packer.allocate(padded_width, padded_height) {
+                placement = Some((page, padded));
+                break;
+            }
+        }
+        if placement.is_none() {
+            let mut packer = ShelfPacker::new(options.max_size, options.max_size);
+            match packer.allocate(padded_width, padded_height) {
+                Some(padded) => placement = Some((packers.len(), padded)),
+                None => return Err(AtlasBuilderError::Texture(Texture2DError::OutOfBounds)),
+            }
+            packers.push(packer);
+        }
+        if let Some(placement) = placement {
+            placements[index] = placement;
+        }
+    }
+
+    // crop every page to the area that was used
+    let mut pages: Vec<AtlasPage> = (0..packers.len())
+        .map(|_| AtlasPage {
+            width: 0,
+            height: 0,
+            data: Vec::new(),
+        })
+        .collect();
+    for &(page, padded) in placements.iter() {
+        let page = &mut pages[page];
+        page.width = page.width.max(padded.x + padded.width);
+        page.height = page.height.max(padded.y + padded.height);
+    }
+    for page in pages.iter_mut() {
+        page.data = vec![0; (page.width * page.height * 4) as usize];
+    }
+
+    // copy the padded images into their pages
+    let mut manifest = AtlasManifest {
+        pages: (0..pages.len())
+            .map(|page| format!("{}_{}.png", page_name, page))
+            .collect(),
+        sprites: HashMap::with_capacity(trimmed.len()),
+    };
+    for ((name, width, height, rect, data), &(page, padded)) in
+        trimmed.into_iter().zip(placements.iter())
+    {
+        let padded_data = pad_image(
+            &data,
+            rect.width as usize,
+            rect.height as usize,
+            4,
+            padding as usize,
+            options.extrude,
+        );
+        let target = &mut pages[page];
+        let row_len = (padded.width * 4) as usize;
+        for row in 0..padded.height {
+            let start = (((padded.y + row) * target.width + padded.x) * 4) as usize;
+            let source = row as usize * row_len;
+            target.data[start..start + row_len]
+                .copy_from_slice(&padded_data[source..source + row_len]);
+        }
+
+        manifest.sprites.insert(
+            name,
+            AtlasSprite {
+                page: page as u32,
+                rect: AtlasRect {
+                    x: padded.x + padding,
+                    y: padded.y + padding,
+                    width: rect.width,
+                    height: rect.height,
+                },
+                trim_x: rect.x,
+                trim_y: rect.y,
+                original_width: width,
+                original_height: height,
+            },
+        );
+    }
+
+    Ok((pages, manifest))
+}
+
+// returns the smallest rectangle containing every pixel that isn't fully transparent
+fn opaque_bounds(width: u32, height: u32, data: &[u8]) -> AtlasRect {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (width, height, 0, 0);
+    for y in 0..height {
+        for x in 0..width {
+            if data[((y * width + x) * 4 + 3) as usize] != 0 {
+                min_x = min_x.min(x);
+                min_y = min_y.min(y);
+                max_x = max_x.max(x + 1);
+                max_y = max_y.max(y + 1);
+            }
+        }
+    }
+
+    // keep a single pixel of fully transparent images
+    if min_x >= max_x || min_y >= max_y {
+        return AtlasRect {
+            x: 0,
+            y: 0,
+            width: width.min(1),
+            height: height.min(1),
+        };
+    }
+
+    AtlasRect {
+        x: min_x,
+        y: min_y,
+        width: max_x - min_x,
+        height: max_y - min_y,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a tightly packed RGBA8 image with the given pixels opaque
+    fn image(width: u32, height: u32, opaque: &[(u32, u32)]) -> Vec<u8> {
+        let mut data = vec![0u8; (width * height * 4) as usize];
+        for &(x, y) in opaque {
+            let start = ((y * width + x) * 4) as usize;
+            data[start..start + 4].copy_from_slice(&[255, 0, 0, 255]);
+        }
+        data
+    }
+
+    #[test]
+    fn opaque_bounds_trims_transparent_border() {
+        let data = image(4, 4, &[(1, 2), (2, 2)]);
+        let bounds = opaque_bounds(4, 4, &data);
+        assert_eq!(
+            bounds,
+            AtlasRect {
+                x: 1,
+                y: 2,
+                width: 2,
+                height: 1
+            }
+        );
+    }
+
+    #[test]
+    fn opaque_bounds_keeps_a_pixel_of_transparent_images() {
+        let data = image(3, 2, &[]);
+        let bounds = opaque_bounds(3, 2, &data);
+        assert_eq!((bounds.width, bounds.height), (1, 1));
+    }
+
+    #[test]
+    fn pack_images_trims_and_places_images() {
+        let images = vec![
+            ("a".to_string(), 4, 4, image(4, 4, &[(1, 1), (2, 2)])),
+            ("b".to_string(), 2, 2, image(2, 2, &[(0, 0), (1, 1)])),
+        ];
+        let (pages, manifest) =
+            pack_images(images, "atlas", AtlasBuilderOptions::default()).unwrap();
+        assert_eq!(pages.len(), 1);
+        assert_eq!(manifest.pages, vec!["atlas_0.png".to_string()]);
+
+        let a = manifest.sprites["a"];
+        assert_eq!(
+            (a.trim_x, a.trim_y, a.rect.width, a.rect.height),
+            (1, 1, 2, 2)
+        );
+        assert_eq!((a.original_width, a.original_height), (4, 4));
+
+        // the first opaque pixel of the trimmed image lands at the sprite rect
+        let page = &pages[0];
+        let start = ((a.rect.y * page.width + a.rect.x) * 4) as usize;
+        assert_eq!(&page.data[start..start + 4], &[255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn pack_images_rejects_images_larger_than_a_page() {
+        let images = vec![("big".to_string(), 4, 4, image(4, 4, &[(0, 0), (3, 3)]))];
+        let options = AtlasBuilderOptions {
+            max_size: 4,
+            ..AtlasBuilderOptions::default()
+        };
+        let result = pack_images(images, "atlas", options);
+        assert!(matches!(
+            result,
+            Err(AtlasBuilderError::Texture(Texture2DError::OutOfBounds))
+        ));
+    }
+
+    #[test]
+    fn pack_images_rejects_duplicate_names() {
+        let images = vec![
+            ("a".to_string(), 1, 1, image(1, 1, &[(0, 0)])),
+            ("a".to_string(), 1, 1, image(1, 1, &[(0, 0)])),
+        ];
+        let result = pack_images(images, "atlas", AtlasBuilderOptions::default());
+        assert!(matches!(result, Err(AtlasBuilderError::DuplicateName)));
+    }
+}
@@ -0,0 +1,506 @@
+use std::collections::HashMap;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io;
+use std::path::*;
+
+use super::atlas_packer::*;
+
+#[derive(Debug)]
+pub enum AtlasManifestError {
+    FileNotFound,
+    CouldNotWriteFile,
+    DecodingError,
+}
+
+/// The text format of an atlas manifest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    Json,
+    Ron,
+}
+
+/// A packed image, trimmed of its transparent border
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasSprite {
+    /// The index of the atlas page holding the image
+    pub page: u32,
+    /// The pixels of the trimmed image within the page
+    pub rect: AtlasRect,
+    /// The offset of the trimmed image within the original image
+    pub trim_x: u32,
+    pub trim_y: u32,
+    pub original_width: u32,
+    pub original_height: u32,
+}
+
+/// Describes the pages of a packed atlas and where each image was packed
+#[derive(Debug, Clone, Default)]
+pub struct AtlasManifest {
+    /// The file names of the page images, relative to the manifest
+    pub pages: Vec<String>,
+    pub sprites: HashMap<String, AtlasSprite>,
+}
+
+impl AtlasManifest {
+    /// Reads a JSON or RON manifest
+    pub fn load(path: &Path) -> Result<Self, AtlasManifestError> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text),
+            // the file isn't valid utf-8
+            Err(ref e) if e.kind() == io::ErrorKind::InvalidData => {
+                Err(AtlasManifestError::DecodingError)
+            }
+            Err(_) => Err(AtlasManifestError::FileNotFound),
+        }
+    }
+
+    pub fn save(&self, path: &Path, format: ManifestFormat) -> Result<(), AtlasManifestError> {
+        match fs::write(path, self.to_text(format)) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(AtlasManifestError::CouldNotWriteFile),
+        }
+    }
+
+    /// Formats the manifest, with sprites sorted by name
+    pub fn to_text(&self, format: ManifestFormat) -> String {
+        let mut names: Vec<&String> = self.sprites.keys().collect();
+        names.sort();
+
+        // json quotes its keys and uses objects for structs, ron uses bare identifiers and tuples
+        let (open, close) = match format {
+            ManifestFormat::Json => ('{', '}'),
+            ManifestFormat::Ron => ('(', ')'),
+        };
+        let field = |text: &mut String, name: &str| {
+            let _ = match format {
+                ManifestFormat::Json => write!(text, "\"{}\": ", name),
+                ManifestFormat::Ron => write!(text, "{}: ", name),
+            };
+        };
+
+        let mut text = String::new();
+        text.push(open);
+        text.push_str("\n    ");
+        field(&mut text, "pages");
+        text.push('[');
+        for (i, page) in self.pages.iter().enumerate() {
+            if i > 0 {
+                text.push_str(", ");
+            }
+            text.push_str(&quote(page));
+        }
+        text.push_str("],\n    ");
+        field(&mut text, "sprites");
+        text.push('{');
+        for (i, name) in names.iter().enumerate() {
+            let sprite = &self.sprites[*name];
+            text.push_str(if i > 0 { ",\n        " } else { "\n        " });
+            text.push_str(&quote(name));
+            text.push_str(": ");
+            text.push(open);
+            let fields = [
+                ("page", sprite.page),
+                ("x", sprite.rect.x),
+                ("y", sprite.rect.y),
+                ("width", sprite.rect.width),
+                ("height", sprite.rect.height),
+                ("trim_x", sprite.trim_x),
+                ("trim_y", sprite.trim_y),
+                ("original_width", sprite.original_width),
+                ("original_height", sprite.original_height),
+            ];
+            for (j, &(name, value)) in fields.iter().enumerate() {
+                if j > 0 {
+                    text.push_str(", ");
+                }
+                field(&mut text, name);
+                let _ = write!(text, "{}", value);
+            }
+            text.push(close);
+        }
+        if !names.is_empty() {
+            text.push_str("\n    ");
+        }
+        text.push_str("}\n");
+        text.push(close);
+        text.push('\n');
+
+        text
+    }
+
+    /// Parses a JSON or RON manifest
+    pub fn parse(text: &str) -> Result<Self, AtlasManifestError> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            pos: 0,
+        };
+        let root = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos != parser.chars.len() {
+            return Err(AtlasManifestError::DecodingError);
+        }
+
+        let mut manifest = AtlasManifest::default();
+        for page in root.get("pages")?.as_list()? {
+            manifest.pages.push(page.as_string()?.to_string());
+        }
+        for (name, sprite) in root.get("sprites")?.as_map()? {
+            let number = |field: &str| sprite.get(field)?.as_u32();
+            manifest.sprites.insert(
+                name.clone(),
+                AtlasSprite {
+                    page: number("page")?,
+                    rect: AtlasRect {
+                        x: number("x")?,
+                        y: number("y")?,
+                        width: number("width")?,
+                        height: number("height")?,
+                    },
+                    trim_x: number("trim_x")?,
+                    trim_y: number("trim_y")?,
+                    original_width: number("original_width")?,
+                    original_height: number("original_height")?,
+                },
+            );
+        }
+
+        Ok(manifest)
+    }
+}
+
+// escapes a string for both json and ron
+fn quote(string: &str) -> String {
+    let mut quoted = String::with_capacity(string.len() + 2);
+    quoted.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// the subset of json and ron used by manifests, structs and maps both parse to Map
+enum Value {
+    Number(f64),
+    String(String),
+    List(Vec<Value>),
+    Map(Vec<(String, Value)>),
+}
+
+impl Value {
+    fn get(&self, key: &str) -> Result<&Value, AtlasManifestError> {
+        self.as_map()?
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+            .ok_or(AtlasManifestError::DecodingError)
+    }
+
+    fn as_map(&self) -> Result<&Vec<(String, Value)>, AtlasManifestError> {
+        match *self {
+            Value::Map(ref map) => Ok(map),
+            _ => Err(AtlasManifestError::DecodingError),
+        }
+    }
+
+    fn as_list(&self) -> Result<&Vec<Value>, AtlasManifestError> {
+        match *self {
+            Value::List(ref list) => Ok(list),
+            _ => Err(AtlasManifestError::DecodingError),
+        }
+    }
+
+    fn as_string(&self) -> Result<&str, AtlasManifestError> {
+        match *self {
+            Value::String(ref string) => Ok(string),
+            _ => Err(AtlasManifestError::DecodingError),
+        }
+    }
+
+    fn as_u32(&self) -> Result<u32, AtlasManifestError> {
+        match *self {
+            Value::Number(number) if number >= 0.0 && number <= u32::MAX as f64 => {
+                Ok(number as u32)
+            }
+            _ => Err(AtlasManifestError::DecodingError),
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.chars.len() {
+            match self.chars[self.pos] {
+                c if c.is_whitespace() => self.pos += 1,
+                // ron line comments
+                '/' if self.chars.get(self.pos + 1) == Some(&'/') => {
+                    while self.pos < self.chars.len() && self.chars[self.pos] != '\n' {
+                        self.pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), AtlasManifestError> {
+        if self.peek() != Some(c) {
+            return Err(AtlasManifestError::DecodingError);
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Value, AtlasManifestError> {
+        match self.peek() {
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('[') => {
+                self.pos += 1;
+                let mut list = Vec::new();
+                while self.peek() != Some(']') {
+                    list.push(self.value()?);
+                    if !self.separator(']')? {
+                        break;
+                    }
+                }
+                self.expect(']')?;
+                Ok(Value::List(list))
+            }
+            Some('{') => self.map('}'),
+            Some('(') => self.map(')'),
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                let start = self.pos;
+                while self.pos < self.chars.len()
+                    && matches!(
+                        self.chars[self.pos],
+                        '-' | '+' | '.' | 'e' | 'E' | '0'..='9'
+                    )
+                {
+                    self.pos += 1;
+                }
+                let number: String = self.chars[start..self.pos].iter().collect();
+                match number.parse() {
+                    Ok(number) => Ok(Value::Number(number)),
+                    Err(_) => Err(AtlasManifestError::DecodingError),
+                }
+            }
+            // ron structs may be prefixed with their name
+            Some(c) if c.is_alphabetic() => {
+                self.identifier();
+                if self.peek() != Some('(') {
+                    return Err(AtlasManifestError::DecodingError);
+                }
+                self.map(')')
+            }
+            _ => Err(AtlasManifestError::DecodingError),
+        }
+    }
+
+    // parses the entries of a json object, ron map or ron struct
+    fn map(&mut self, close: char) -> Result<Value, AtlasManifestError> {
+        self.pos += 1;
+        let mut map = Vec::new();
+        while self.peek() != Some(close) {
+            let key = match self.peek() {
+                Some('"') => self.string()?,
+                Some(c) if c.is_alphabetic() || c == '_' => self.identifier(),
+                _ => return Err(AtlasManifestError::DecodingError),
+            };
+            self.expect(':')?;
+            map.push((key, self.value()?));
+            if !self.separator(close)? {
+                break;
+            }
+        }
+        self.expect(close)?;
+        Ok(Value::Map(map))
+    }
+
+    // consumes a comma, returning false if the collection ends instead
+    fn separator(&mut self, close: char) -> Result<bool, AtlasManifestError> {
+        match self.peek() {
+            Some(',') => {
+                self.pos += 1;
+                Ok(true)
+            }
+            Some(c) if c == close => Ok(false),
+            _ => Err(AtlasManifestError::DecodingError),
+        }
+    }
+
+    fn identifier(&mut self) -> String {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self.pos < self.chars.len()
+            && (self.chars[self.pos].is_alphanumeric() || self.chars[self.pos] == '_')
+        {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn string(&mut self) -> Result<String, AtlasManifestError> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            let c = match self.chars.get(self.pos) {
+                Some(&c) => c,
+                None => return Err(AtlasManifestError::DecodingError),
+            };
+            self.pos += 1;
+            match c {
+                '"' => return Ok(string),
+                '\\' => {
+                    let escaped = match self.chars.get(self.pos) {
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('u') => {
+                            self.pos += 1;
+                            string.push(self.unicode_escape()?);
+                            continue;
+                        }
+                        Some(&c) => c,
+                        None => return Err(AtlasManifestError::DecodingError),
+                    };
+                    self.pos += 1;
+                    string.push(escaped);
+                }
+                c => string.push(c),
+            }
+        }
+    }
+
+    // parses the XXXX of a \uXXXX escape, combining utf-16 surrogate pairs
+    fn unicode_escape(&mut self) -> Result<char, AtlasManifestError> {
+        let high = self.hex4()?;
+        let code = if (0xd800..0xdc00).contains(&high) {
+            if self.chars.get(self.pos) != Some(&'\\') || self.chars.get(self.pos + 1) != Some(&'u')
+            {
+                return Err(AtlasManifestError::DecodingError);
+            }
+            self.pos += 2;
+            let low = self.hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(AtlasManifestError::DecodingError);
+            }
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+        std::char::from_u32(code).ok_or(AtlasManifestError::DecodingError)
+    }
+
+    // parses four hex digits
+    fn hex4(&mut self) -> Result<u32, AtlasManifestError> {
+        let digits: String = match self.chars.get(self.pos..self.pos + 4) {
+            Some(digits) => digits.iter().collect(),
+            None => return Err(AtlasManifestError::DecodingError),
+        };
+        self.pos += 4;
+        match u32::from_str_radix(&digits, 16) {
+            Ok(code) if digits.chars().all(|c| c.is_ascii_hexdigit()) => Ok(code),
+            _ => Err(AtlasManifestError::DecodingError),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest() -> AtlasManifest {
+        let mut manifest = AtlasManifest {
+            pages: vec!["atlas_0.png".to_string(), "atlas \"1\".png".to_string()],
+            sprites: HashMap::new(),
+        };
+        let names = [
+            "player",
+            "tab\there",
+            "line\nbreak",
+            "bell\u{7}",
+            "snow\u{2603}",
+        ];
+        for (i, name) in names.iter().enumerate() {
+            let i = i as u32;
+            manifest.sprites.insert(
+                name.to_string(),
+                AtlasSprite {
+                    page: i % 2,
+                    rect: AtlasRect {
+                        x: i * 10,
+                        y: i * 20,
+                        width: 8 + i,
+                        height: 16 + i,
+                    },
+                    trim_x: i,
+                    trim_y: 2 * i,
+                    original_width: 32,
+                    original_height: 64,
+                },
+            );
+        }
+        manifest
+    }
+
+    fn round_trip(format: ManifestFormat) {
+        let manifest = manifest();
+        let text = manifest.to_text(format);
+        assert!(!text.contains('\t') && !text.contains('\u{7}'));
+
+        let parsed = AtlasManifest::parse(&text).unwrap();
+        assert_eq!(parsed.pages, manifest.pages);
+        assert_eq!(parsed.sprites, manifest.sprites);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        round_trip(ManifestFormat::Json);
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        round_trip(ManifestFormat::Ron);
+    }
+
+    #[test]
+    fn parses_unicode_escapes() {
+        let text = r#"{"pages": ["a\u00e9\ud83d\ude00"], "sprites": {}}"#;
+        let manifest = AtlasManifest::parse(text).unwrap();
+        assert_eq!(manifest.pages, vec!["a\u{e9}\u{1f600}".to_string()]);
+
+        let lone_surrogate = r#"{"pages": ["\ud83d"], "sprites": {}}"#;
+        assert!(AtlasManifest::parse(lone_surrogate).is_err());
+    }
+
+    #[test]
+    fn ron_struct_names_need_parentheses() {
+        let named = "Manifest(pages: [], sprites: {})";
+        assert!(AtlasManifest::parse(named).is_ok());
+        assert!(AtlasManifest::parse("Manifest{pages: [], sprites: {}}").is_err());
+        assert!(AtlasManifest::parse("Manifest").is_err());
+    }
+}
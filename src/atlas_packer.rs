@@ -0,0 +1,270 @@
+use std::collections::HashSet;
+
+/// A rectangle of pixels, with (0, 0) at the first pixel of the image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AtlasRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// a row of rectangles sharing a height, with the horizontal spans that are still free
+#[derive(Debug, Clone)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    free: Vec<(u32, u32)>, // x, width, sorted by x
+}
+
+/// Packs rectangles into rows of similar height, without touching the GL
+///
+/// Freed rectangles are returned to their row and reused by later allocations.
+#[derive(Debug, Clone)]
+pub struct ShelfPacker {
+    width: u32,
+    height: u32,
+    shelves: Vec<Shelf>,
+    allocated: HashSet<AtlasRect>,
+}
+
+impl ShelfPacker {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            shelves: Vec::new(),
+            allocated: HashSet::new(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Finds room for a rectangle, returning None if the packer is full
+    pub fn allocate(&mut self, width: u32, height: u32) -> Option<AtlasRect> {
+        if width > self.width || height > self.height {
+            return None;
+        }
+
+        // find the shelf that wastes the least height
+        let mut best = None;
+        for (shelf_index, shelf) in self.shelves.iter().enumerate() {
+            if shelf.height < height {
+                continue;
+            }
+            let waste = shelf.height - height;
+            if let Some((_, _, best_waste)) = best {
+                if waste >= best_waste {
+                    continue;
+                }
+            }
+            if let Some(span_index) = shelf.free.iter().position(|&(_, span)| span >= width) {
+                best = Some((shelf_index, span_index, waste));
+            }
+        }
+
+        // prefer opening a new shelf over wasting more than half of an existing one
+        let top = self
+            .shelves
+            .last()
+            .map_or(0, |shelf| shelf.y + shelf.height);
+        let can_open = top + height <= self.height;
+        let (shelf_index, span_index) = match best {
+            Some((shelf_index, span_index, waste)) if waste <= height / 2 || !can_open => {
+                (shelf_index, span_index)
+            }
+            _ if can_open => {
+                self.shelves.push(Shelf {
+                    y: top,
+                    height,
+                    free: vec![(0, self.width)],
+                });
+                (self.shelves.len() - 1, 0)
+            }
+            _ => return None,
+        };
+
+        // take the rectangle from the left of the span
+        let shelf = &mut self.shelves[shelf_index];
+        let (x, span) = shelf.free[span_index];
+        if span == width {
+            shelf.free.remove(span_index);
+        } else {
+            shelf.free[span_index] = (x + width, span - width);
+        }
+
+        let rect = AtlasRect {
+            x,
+            y: shelf.y,
+            width,
+            height,
+        };
+        self.allocated.insert(rect);
+        Some(rect)
+    }
+
+    /// Returns a rectangle from `allocate` to the packer
+    ///
+    /// Returns false without changing anything if the rectangle isn't currently allocated.
+    pub fn free(&mut self, rect: AtlasRect) -> bool {
+        if !self.allocated.remove(&rect) {
+            return false;
+        }
+        let shelf = match self.shelves.iter_mut().find(|shelf| shelf.y == rect.y) {
+            Some(shelf) => shelf,
+            None => return false,
+        };
+
+        // insert the span and merge it with its neighbours
+        let index = shelf
+            .free
+            .iter()
+            .position(|&(x, _)| x > rect.x)
+            .unwrap_or(shelf.free.len());
+        shelf.free.insert(index, (rect.x, rect.width));
+        if index + 1 < shelf.free.len() && rect.x + rect.width == shelf.free[index + 1].0 {
+            shelf.free[index].1 += shelf.free[index + 1].1;
+            shelf.free.remove(index + 1);
+        }
+        if index > 0 && shelf.free[index - 1].0 + shelf.free[index - 1].1 == rect.x {
+            shelf.free[index - 1].1 += shelf.free[index].1;
+            shelf.free.remove(index);
+        }
+
+        // empty shelves at the top can be reopened with a different height
+        let width = self.width;
+        while let Some(shelf) = self.shelves.last() {
+            if shelf.free != [(0, width)] {
+                break;
+            }
+            self.shelves.pop();
+        }
+
+        true
+    }
+
+    /// Enlarges the packing area, every allocated rectangle stays in place
+    pub fn grow(&mut self, width: u32, height: u32) {
+        if width > self.width {
+            for shelf in self.shelves.iter_mut() {
+                match shelf.free.last_mut() {
+                    Some(span) if span.0 + span.1 == self.width => span.1 += width - self.width,
+                    _ => shelf.free.push((self.width, width - self.width)),
+                }
+            }
+            self.width = width;
+        }
+        self.height = self.height.max(height);
+    }
+}
+
+// surrounds an image with `padding` pixels, either transparent or repeating its edges
+pub(super) fn pad_image(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    bytes_per_color: usize,
+    padding: usize,
+    extrude: bool,
+) -> Vec<u8> {
+    if padding == 0 {
+        return data.to_vec();
+    }
+
+    let padded_width = width + 2 * padding;
+    let padded_height = height + 2 * padding;
+    let mut padded = vec![0u8; padded_width * padded_height * bytes_per_color];
+    for y in 0..padded_height {
+        // rows above and below the image repeat its first and last rows when extruding
+        let source_y = match y.checked_sub(padding) {
+            Some(source_y) if source_y < height => source_y,
+            Some(_) if extrude && height > 0 => height - 1,
+            None if extrude && height > 0 => 0,
+            _ => continue,
+        };
+        for x in 0..padded_width {
+            let source_x = match x.checked_sub(padding) {
+                Some(source_x) if source_x < width => source_x,
+                Some(_) if extrude && width > 0 => width - 1,
+                None if extrude && width > 0 => 0,
+                _ => continue,
+            };
+            let source = (source_y * width + source_x) * bytes_per_color;
+            let target = (y * padded_width + x) * bytes_per_color;
+            padded[target..target + bytes_per_color]
+                .copy_from_slice(&data[source..source + bytes_per_color]);
+        }
+    }
+
+    padded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: u32, y: u32, width: u32, height: u32) -> AtlasRect {
+        AtlasRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn shelf_packer_allocate_free_grow() {
+        let mut packer = ShelfPacker::new(8, 8);
+        assert_eq!(packer.allocate(4, 4), Some(rect(0, 0, 4, 4)));
+        assert_eq!(packer.allocate(4, 4), Some(rect(4, 0, 4, 4)));
+        assert_eq!(packer.allocate(4, 4), Some(rect(0, 4, 4, 4)));
+        assert_eq!(packer.allocate(4, 4), Some(rect(4, 4, 4, 4)));
+        assert_eq!(packer.allocate(1, 1), None);
+
+        // freed rectangles are reused, but only once
+        assert!(packer.free(rect(4, 0, 4, 4)));
+        assert!(!packer.free(rect(4, 0, 4, 4)));
+        assert!(!packer.free(rect(0, 0, 2, 2)));
+        assert_eq!(packer.allocate(4, 4), Some(rect(4, 0, 4, 4)));
+
+        // growing keeps the rectangles in place and extends every shelf
+        packer.grow(16, 8);
+        assert_eq!((packer.width(), packer.height()), (16, 8));
+        assert_eq!(packer.allocate(8, 4), Some(rect(8, 0, 8, 4)));
+        assert_eq!(packer.allocate(8, 4), Some(rect(8, 4, 8, 4)));
+        assert_eq!(packer.allocate(1, 1), None);
+    }
+
+    #[test]
+    fn shelf_packer_reopens_empty_shelves() {
+        let mut packer = ShelfPacker::new(4, 4);
+        let small = packer.allocate(4, 1).unwrap();
+        assert!(packer.free(small));
+        assert_eq!(packer.allocate(4, 4), Some(rect(0, 0, 4, 4)));
+    }
+
+    #[test]
+    fn pad_image_transparent() {
+        let padded = pad_image(&[1, 2], 2, 1, 1, 1, false);
+        assert_eq!(padded, vec![0, 0, 0, 0, 0, 1, 2, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn pad_image_extrude() {
+        let padded = pad_image(&[1, 2, 3, 4], 2, 2, 1, 1, true);
+        #[rustfmt::skip]
+        let expected = vec![
+            1, 1, 2, 2,
+            1, 1, 2, 2,
+            3, 3, 4, 4,
+            3, 3, 4, 4,
+        ];
+        assert_eq!(padded, expected);
+    }
+}
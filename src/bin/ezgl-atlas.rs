@@ -0,0 +1,79 @@
+extern crate ezgl;
+
+use std::env;
+use std::fs;
+use std::path::*;
+use std::process;
+
+use ezgl::*;
+
+const USAGE: &str = "usage: ezgl-atlas <input dir> <output dir> [--name atlas] [--size 2048] \
+                     [--padding 1] [--extrude] [--no-trim] [--ron]";
+
+fn main() {
+    if let Err(message) = run() {
+        eprintln!("ezgl-atlas: {}", message);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut paths = Vec::new();
+    let mut name = String::from("atlas");
+    let mut options = AtlasBuilderOptions::default();
+    let mut format = ManifestFormat::Json;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--name" => name = args.next().ok_or(USAGE)?,
+            "--size" => options.max_size = number(args.next())?,
+            "--padding" => options.padding = number(args.next())?,
+            "--extrude" => options.extrude = true,
+            "--no-trim" => options.trim = false,
+            "--ron" => format = ManifestFormat::Ron,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}\n{}", arg, USAGE)),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.len() != 2 {
+        return Err(USAGE.to_string());
+    }
+    let (input, output) = (&paths[0], &paths[1]);
+
+    let (pages, manifest) = pack_directory(input, &name, options)
+        .map_err(|e| format!("could not pack {}: {:?}", input.display(), e))?;
+
+    fs::create_dir_all(output)
+        .map_err(|e| format!("could not create {}: {}", output.display(), e))?;
+    for (page, file_name) in pages.iter().zip(manifest.pages.iter()) {
+        page.save_png(&output.join(file_name))
+            .map_err(|e| format!("could not write {}: {:?}", file_name, e))?;
+    }
+    let manifest_name = match format {
+        ManifestFormat::Json => format!("{}.json", name),
+        ManifestFormat::Ron => format!("{}.ron", name),
+    };
+    manifest
+        .save(&output.join(&manifest_name), format)
+        .map_err(|e| format!("could not write {}: {:?}", manifest_name, e))?;
+
+    println!(
+        "packed {} images into {} pages",
+        manifest.sprites.len(),
+        pages.len()
+    );
+
+    Ok(())
+}
+
+fn number(arg: Option<String>) -> Result<u32, String> {
+    match arg.map(|arg| arg.parse()) {
+        Some(Ok(number)) => Ok(number),
+        _ => Err(USAGE.to_string()),
+    }
+}
@@ -2,6 +2,10 @@ use gl;
 use gl::types::*;
 
 use std::marker::PhantomData;
+use std::mem;
+use std::ops::Range;
+use std::ptr::NonNull;
+use std::slice;
 
 use super::gl_buffer_resource::*;
 use std;
@@ -9,11 +13,161 @@ use std;
 #[derive(Debug)]
 pub enum BufferError {
     BufferNotInitialized,
+    OutOfBounds,
+    InvalidMapAccess,
+    MapFailed,
+    /// The contents of a mapped buffer were lost while it was mapped
+    DataCorrupted,
+    /// Waiting for the GL to finish reading a buffer failed
+    SyncFailed,
+    /// The program has no uniform block of the given name
+    InvalidUniformBlock,
 }
 
+/// A hint describing how often the contents of a buffer change and who reads them
+///
+/// Draw buffers are written by the application and read by the GL, read buffers are written
+/// by the GL and read by the application, and copy buffers are both written and read by the GL.
+/// Static buffers are written once, dynamic buffers are written repeatedly and stream buffers
+/// are written about as often as they're used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferUsage {
+    StaticDraw,
+    StaticRead,
+    StaticCopy,
+    DynamicDraw,
+    DynamicRead,
+    DynamicCopy,
+    StreamDraw,
+    StreamRead,
+    StreamCopy,
+}
+
+impl BufferUsage {
+    pub fn to_gl(self) -> GLenum {
+        match self {
+            BufferUsage::StaticDraw => gl::STATIC_DRAW,
+            BufferUsage::StaticRead => gl::STATIC_READ,
+            BufferUsage::StaticCopy => gl::STATIC_COPY,
+            BufferUsage::DynamicDraw => gl::DYNAMIC_DRAW,
+            BufferUsage::DynamicRead => gl::DYNAMIC_READ,
+            BufferUsage::DynamicCopy => gl::DYNAMIC_COPY,
+            BufferUsage::StreamDraw => gl::STREAM_DRAW,
+            BufferUsage::StreamRead => gl::STREAM_READ,
+            BufferUsage::StreamCopy => gl::STREAM_COPY,
+        }
+    }
+}
+
+/// How a mapped range of a buffer is accessed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MapAccess {
+    pub read: bool,
+    pub write: bool,
+    /// Discards the previous contents of the range, only valid without read access
+    pub invalidate_range: bool,
+    /// Skips waiting for pending draws using the buffer, the caller must not overwrite
+    /// anything they're still reading
+    pub unsynchronized: bool,
+    /// Only makes writes visible to the GL once passed to `BufferMapping::flush`
+    pub explicit_flush: bool,
+}
+
+impl MapAccess {
+    pub fn read() -> Self {
+        Self {
+            read: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn write() -> Self {
+        Self {
+            write: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn read_write() -> Self {
+        Self {
+            read: true,
+            write: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_invalidate_range(mut self) -> Self {
+        self.invalidate_range = true;
+        self
+    }
+
+    pub fn with_unsynchronized(mut self) -> Self {
+        self.unsynchronized = true;
+        self
+    }
+
+    pub fn with_explicit_flush(mut self) -> Self {
+        self.explicit_flush = true;
+        self
+    }
+
+    // returns the access bits, or None for combinations the GL rejects
+    fn to_gl(self) -> Option<GLbitfield> {
+        if !(self.read || self.write)
+            || (self.invalidate_range && self.read)
+            || (self.explicit_flush && !self.write)
+        {
+            return None;
+        }
+
+        let flags = [
+            (self.read, gl::MAP_READ_BIT),
+            (self.write, gl::MAP_WRITE_BIT),
+            (self.invalidate_range, gl::MAP_INVALIDATE_RANGE_BIT),
+            (self.unsynchronized, gl::MAP_UNSYNCHRONIZED_BIT),
+            (self.explicit_flush, gl::MAP_FLUSH_EXPLICIT_BIT),
+        ];
+        Some(
+            flags
+                .iter()
+                .filter(|&&(set, _)| set)
+                .fold(0, |bits, &(_, bit)| bits | bit),
+        )
+    }
+}
+
+/// Element types that are valid for every bit pattern, so mapped memory can be viewed as them
+///
+/// # Safety
+///
+/// Any bytes written by the GL must form a valid value, which rules out `bool`, `char`,
+/// references and most enums. Structs made of plain data fields may implement it.
+pub unsafe trait PlainData: Copy {}
+
+unsafe impl PlainData for u8 {}
+unsafe impl PlainData for u16 {}
+unsafe impl PlainData for u32 {}
+unsafe impl PlainData for u64 {}
+unsafe impl PlainData for i8 {}
+unsafe impl PlainData for i16 {}
+unsafe impl PlainData for i32 {}
+unsafe impl PlainData for i64 {}
+unsafe impl PlainData for f32 {}
+unsafe impl PlainData for f64 {}
+unsafe impl<T: PlainData> PlainData for (T, T) {}
+unsafe impl<T: PlainData> PlainData for (T, T, T) {}
+unsafe impl<T: PlainData> PlainData for (T, T, T, T) {}
+unsafe impl<T: PlainData, const N: usize> PlainData for [T; N] {}
+
+/// A GL buffer holding `len` elements of type T, with storage for `capacity` elements
 pub struct Buffer<T> {
     phantom: PhantomData<T>,
     pub(super) buffer_type: GLenum,
+    pub(super) usage: BufferUsage,
+    pub(super) len: usize,
+    pub(super) capacity: usize,
+    // the highest index written to an element buffer, None if unknown or not an element buffer
+    pub(super) max_index: Option<usize>,
     pub(super) resource: GLBufferResource,
 }
 
@@ -22,15 +176,23 @@ impl<T> Buffer<T> {
         Self {
             phantom: PhantomData,
             buffer_type: 0,
+            usage: BufferUsage::StaticDraw,
+            len: 0,
+            capacity: 0,
+            max_index: None,
             resource: GLBufferResource::new(),
         }
     }
 
-    pub fn from(buffer_type: GLenum, data: &[T]) -> Self {
+    pub fn from(buffer_type: GLenum, data: &[T], usage: BufferUsage) -> Self {
         // init
         let s = Self {
             phantom: PhantomData,
             buffer_type,
+            usage,
+            len: data.len(),
+            capacity: data.len(),
+            max_index: max_index(buffer_type, data),
             resource: GLBufferResource::new(),
         };
 
@@ -41,14 +203,19 @@ impl<T> Buffer<T> {
                 buffer_type,
                 (data.len() * std::mem::size_of::<T>()) as _,
                 data.as_ptr() as _,
-                gl::STATIC_DRAW,
+                usage.to_gl(),
             );
         }
 
         s
     }
 
-    pub fn init(&mut self, buffer_type: GLenum, data: &[T]) -> Result<(), BufferError> {
+    pub fn init(
+        &mut self,
+        buffer_type: GLenum,
+        data: &[T],
+        usage: BufferUsage,
+    ) -> Result<(), BufferError> {
         // upload the data
         unsafe {
             gl::BindBuffer(buffer_type, self.resource.get_raw());
@@ -56,32 +223,61 @@ impl<T> Buffer<T> {
                 buffer_type,
                 (data.len() * std::mem::size_of::<T>()) as _,
                 data.as_ptr() as _,
-                gl::STATIC_DRAW,
+                usage.to_gl(),
             );
         }
 
         self.buffer_type = buffer_type;
+        self.usage = usage;
+        self.len = data.len();
+        self.capacity = data.len();
+        self.max_index = max_index(buffer_type, data);
 
         Ok(())
     }
 
-    pub fn init_null(&mut self, buffer_type: GLenum, len: usize) -> Result<(), BufferError> {
+    /// Allocates storage for `capacity` elements, the buffer is empty until data is spliced in
+    pub fn init_null(
+        &mut self,
+        buffer_type: GLenum,
+        capacity: usize,
+        usage: BufferUsage,
+    ) -> Result<(), BufferError> {
         // upload the data
         unsafe {
             gl::BindBuffer(buffer_type, self.resource.get_raw());
             gl::BufferData(
                 buffer_type,
-                (len * std::mem::size_of::<T>()) as _,
+                (capacity * std::mem::size_of::<T>()) as _,
                 0 as _,
-                gl::STATIC_DRAW,
+                usage.to_gl(),
             );
         }
 
         self.buffer_type = buffer_type;
+        self.usage = usage;
+        self.len = 0;
+        self.capacity = capacity;
+        self.max_index = None;
 
         Ok(())
     }
 
+    /// The number of elements written to the buffer
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of elements the buffer has storage for
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Overwrites elements starting at `pos`, extending the length if writing past it
     pub fn splice(&mut self, pos: usize, data: &[T]) -> Result<(), BufferError>
     where
         T: Copy,
@@ -91,6 +287,10 @@ impl<T> Buffer<T> {
             0 => return Err(BufferError::BufferNotInitialized),
             _ => {}
         }
+        let end = match pos.checked_add(data.len()) {
+            Some(end) if end <= self.capacity => end,
+            _ => return Err(BufferError::OutOfBounds),
+        };
 
         // upload the data
         unsafe {
@@ -102,7 +302,212 @@ impl<T> Buffer<T> {
                 data.as_ptr() as _,
             );
         }
+        // overwritten indices may have been higher, so the result is an upper bound
+        let written = max_index(self.buffer_type, data);
+        self.max_index = if self.len == 0 {
+            written
+        } else {
+            self.max_index
+                .map(|max| written.map_or(max, |written| written.max(max)))
+        };
+        self.len = self.len.max(end);
+
+        Ok(())
+    }
+
+    /// Replaces the contents of the buffer with new storage, so the GL doesn't wait for draws
+    /// still reading the old contents
+    ///
+    /// Intended for data rewritten every frame, with a dynamic or stream usage.
+    pub fn orphan_and_upload(&mut self, data: &[T]) -> Result<(), BufferError>
+    where
+        T: Copy,
+    {
+        // check for vailidity
+        if self.buffer_type == 0 {
+            return Err(BufferError::BufferNotInitialized);
+        }
+
+        // orphan the old storage, the driver frees it once pending draws are done
+        let size = std::mem::size_of_val(data) as _;
+        unsafe {
+            gl::BindBuffer(self.buffer_type, self.resource.get_raw());
+            gl::BufferData(self.buffer_type, size, 0 as _, self.usage.to_gl());
+            gl::BufferSubData(self.buffer_type, 0, size, data.as_ptr() as _);
+        }
+        self.len = data.len();
+        self.capacity = data.len();
+        self.max_index = max_index(self.buffer_type, data);
+
+        Ok(())
+    }
+
+    /// Maps a range of elements into memory, it's unmapped when the guard is dropped or passed
+    /// to `BufferMapping::unmap`
+    ///
+    /// The range may extend past the length up to the capacity, mapping it with write access
+    /// extends the length to the end of the range. Only PlainData elements can be mapped, since
+    /// the mapped memory may hold any bytes.
+    pub fn map_range<'a>(
+        &'a mut self,
+        range: Range<usize>,
+        access: MapAccess,
+    ) -> Result<BufferMapping<'a, T>, BufferError>
+    where
+        T: PlainData,
+    {
+        // check for vailidity
+        if self.buffer_type == 0 {
+            return Err(BufferError::BufferNotInitialized);
+        }
+        if range.start > range.end || range.end > self.capacity {
+            return Err(BufferError::OutOfBounds);
+        }
+        let bits = match access.to_gl() {
+            Some(bits) => bits,
+            None => return Err(BufferError::InvalidMapAccess),
+        };
+
+        // the gl can't map empty ranges
+        let len = range.end - range.start;
+        let ptr = if len == 0 {
+            NonNull::dangling().as_ptr()
+        } else {
+            let size = std::mem::size_of::<T>();
+            let ptr = unsafe {
+                gl::BindBuffer(self.buffer_type, self.resource.get_raw());
+                gl::MapBufferRange(
+                    self.buffer_type,
+                    (range.start * size) as _,
+                    (len * size) as _,
+                    bits,
+                )
+            };
+            if ptr.is_null() {
+                return Err(BufferError::MapFailed);
+            }
+            ptr as *mut T
+        };
+        if access.write {
+            // the written indices can't be tracked
+            self.len = self.len.max(range.end);
+            self.max_index = None;
+        }
+
+        Ok(BufferMapping {
+            buffer: self,
+            access,
+            ptr,
+            len,
+        })
+    }
+}
+
+// returns the highest index in data written to an element buffer, None for other buffers
+pub(super) fn max_index<T>(buffer_type: GLenum, data: &[T]) -> Option<usize> {
+    if buffer_type != gl::ELEMENT_ARRAY_BUFFER || mem::size_of::<T>() != mem::align_of::<T>() {
+        return None;
+    }
+
+    // element types are plain unsigned integers of 1, 2 or 4 bytes
+    let bytes =
+        unsafe { slice::from_raw_parts(data.as_ptr() as *const u8, mem::size_of_val(data)) };
+    match mem::size_of::<T>() {
+        1 => bytes.iter().map(|&index| index as usize).max(),
+        2 => bytes
+            .chunks_exact(2)
+            .map(|index| u16::from_ne_bytes([index[0], index[1]]) as usize)
+            .max(),
+        4 => bytes
+            .chunks_exact(4)
+            .map(|index| u32::from_ne_bytes([index[0], index[1], index[2], index[3]]) as usize)
+            .max(),
+        _ => None,
+    }
+}
+
+/// A mapped range of a Buffer, giving access to its elements as allowed by its MapAccess
+///
+/// Dropping the mapping unmaps it without reporting lost contents, use `unmap` to check.
+pub struct BufferMapping<'a, T: 'a> {
+    buffer: &'a mut Buffer<T>,
+    access: MapAccess,
+    ptr: *mut T,
+    len: usize,
+}
+
+impl<'a, T> BufferMapping<'a, T> {
+    /// Makes writes to a range of the mapped elements visible to the GL, with indices relative
+    /// to the start of the mapping
+    pub fn flush(&mut self, range: Range<usize>) -> Result<(), BufferError> {
+        if !self.access.explicit_flush {
+            return Err(BufferError::InvalidMapAccess);
+        }
+        if range.start > range.end || range.end > self.len {
+            return Err(BufferError::OutOfBounds);
+        }
+        if range.start == range.end {
+            return Ok(());
+        }
+
+        let size = std::mem::size_of::<T>();
+        unsafe {
+            gl::BindBuffer(self.buffer.buffer_type, self.buffer.resource.get_raw());
+            gl::FlushMappedBufferRange(
+                self.buffer.buffer_type,
+                (range.start * size) as _,
+                ((range.end - range.start) * size) as _,
+            );
+        }
 
         Ok(())
     }
+
+    /// Returns the mapped elements, the mapping needs read access
+    pub fn as_slice(&self) -> Result<&[T], BufferError> {
+        if !self.access.read {
+            return Err(BufferError::InvalidMapAccess);
+        }
+        Ok(unsafe { slice::from_raw_parts(self.ptr, self.len) })
+    }
+
+    /// Returns the mapped elements for writing, the mapping needs write access
+    ///
+    /// The elements are undefined unless the mapping also has read access.
+    pub fn as_mut_slice(&mut self) -> Result<&mut [T], BufferError> {
+        if !self.access.write {
+            return Err(BufferError::InvalidMapAccess);
+        }
+        Ok(unsafe { slice::from_raw_parts_mut(self.ptr, self.len) })
+    }
+
+    /// Unmaps the range, returning an error if its contents were lost while mapped
+    ///
+    /// The GL may discard mapped contents, e.g. when the display mode changes, after which the
+    /// range has to be written again.
+    pub fn unmap(mut self) -> Result<(), BufferError> {
+        let unmapped = self.unmap_raw();
+        mem::forget(self);
+        match unmapped {
+            true => Ok(()),
+            false => Err(BufferError::DataCorrupted),
+        }
+    }
+
+    // returns false if the GL reports the contents as corrupted
+    fn unmap_raw(&mut self) -> bool {
+        if self.len == 0 {
+            return true;
+        }
+        unsafe {
+            gl::BindBuffer(self.buffer.buffer_type, self.buffer.resource.get_raw());
+            gl::UnmapBuffer(self.buffer.buffer_type) == gl::TRUE
+        }
+    }
+}
+
+impl<'a, T> Drop for BufferMapping<'a, T> {
+    fn drop(&mut self) {
+        self.unmap_raw();
+    }
 }
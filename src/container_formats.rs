@@ -0,0 +1,381 @@
+use gl;
+use gl::types::*;
+
+use super::extensions::*;
+use super::texture::*;
+use super::texture_format::*;
+
+/// How the pixels of a container image are stored
+#[derive(Debug, Clone, Copy)]
+pub(super) struct PixelStorage {
+    pub format: TextureFormat,
+    // the size of a 4x4 block of a compressed format
+    block_bytes: usize,
+    // the file stores blue before red
+    bgr: bool,
+}
+
+impl PixelStorage {
+    // the size of one image of a mip level, None if it doesn't fit in a usize
+    fn level_size(&self, width: u32, height: u32, level: usize) -> Option<usize> {
+        let width = (width >> level).max(1) as usize;
+        let height = (height >> level).max(1) as usize;
+        match self.format.bytes_per_pixel() {
+            Some(bytes_per_pixel) => width
+                .checked_mul(height)?
+                .checked_mul(bytes_per_pixel as usize),
+            None => width
+                .div_ceil(4)
+                .checked_mul(height.div_ceil(4))?
+                .checked_mul(self.block_bytes),
+        }
+    }
+
+    fn read_image(&self, data: &[u8]) -> Vec<u8> {
+        let mut image = data.to_vec();
+        if self.bgr {
+            let bytes_per_pixel = self.format.bytes_per_pixel().unwrap_or(1) as usize;
+            image
+                .chunks_mut(bytes_per_pixel)
+                .for_each(|pixel| pixel.swap(0, 2));
+        }
+        image
+    }
+}
+
+/// A texture container holding every mip level of every layer and cube face
+pub(super) struct ContainerImage {
+    pub width: u32,
+    pub height: u32,
+    pub layers: u32,
+    pub faces: u32,
+    pub storage: PixelStorage,
+    /// Indexed by [level][layer * faces + face]
+    pub levels: Vec<Vec<Vec<u8>>>,
+}
+
+impl ContainerImage {
+    /// Returns an error if the context can't upload the pixel storage of the image
+    pub fn check_support(&self) -> Result<(), Texture2DError> {
+        match self.storage.format {
+            TextureFormat::Compressed(internal_format) => {
+                if is_compressed_format_supported(internal_format) {
+                    Ok(())
+                } else {
+                    Err(Texture2DError::FormatNotSupportedByContext)
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Uploads an image of a mip level to a 2D target or cube face, or to `layer` of a 2D array
+    ///
+    /// The texture must be bound and the unpack state reset.
+    pub unsafe fn upload_image(
+        &self,
+        target: GLenum,
+        level: usize,
+        layer: Option<u32>,
+        image: &[u8],
+    ) {
+        let width = self.width.checked_shr(level as u32).unwrap_or(0).max(1);
+        let height = self.height.checked_shr(level as u32).unwrap_or(0).max(1);
+        let format = self.storage.format;
+        match (layer, format.is_compressed()) {
+            (None, true) => gl::CompressedTexSubImage2D(
+                target,
+                level as _,
+                0,
+                0,
+                width as _,
+                height as _,
+                format.internal_format(),
+                image.len() as _,
+                image.as_ptr() as _,
+            ),
+            (None, false) => gl::TexSubImage2D(
+                target,
+                level as _,
+                0,
+                0,
+                width as _,
+                height as _,
+                format.pixel_format(),
+                format.component_type(),
+                image.as_ptr() as _,
+            ),
+            (Some(layer), true) => gl::CompressedTexSubImage3D(
+                target,
+                level as _,
+                0,
+                0,
+                layer as _,
+                width as _,
+                height as _,
+                1,
+                format.internal_format(),
+                image.len() as _,
+                image.as_ptr() as _,
+            ),
+            (Some(layer), false) => gl::TexSubImage3D(
+                target,
+                level as _,
+                0,
+                0,
+                layer as _,
+                width as _,
+                height as _,
+                1,
+                format.pixel_format(),
+                format.component_type(),
+                image.as_ptr() as _,
+            ),
+        }
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, Texture2DError> {
+    match data.get(offset..offset + 4) {
+        Some(bytes) => Ok(bytes[0] as u32
+            | (bytes[1] as u32) << 8
+            | (bytes[2] as u32) << 16
+            | (bytes[3] as u32) << 24),
+        None => Err(Texture2DError::DecodingError),
+    }
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, Texture2DError> {
+    Ok(read_u32(data, offset)? as u64 | (read_u32(data, offset + 4)? as u64) << 32)
+}
+
+// returns the number of images in each level, rejecting more levels than the size allows
+fn check_levels(
+    width: u32,
+    height: u32,
+    layers: u32,
+    faces: u32,
+    level_count: u32,
+) -> Result<usize, Texture2DError> {
+    if level_count > mip_levels(width, height) {
+        return Err(Texture2DError::DecodingError);
+    }
+    match layers.checked_mul(faces) {
+        Some(images) => Ok(images as usize),
+        None => Err(Texture2DError::DecodingError),
+    }
+}
+
+fn uncompressed(format: TextureFormat) -> PixelStorage {
+    PixelStorage {
+        format,
+        block_bytes: 0,
+        bgr: false,
+    }
+}
+
+fn uncompressed_bgr(format: TextureFormat) -> PixelStorage {
+    PixelStorage {
+        format,
+        block_bytes: 0,
+        bgr: true,
+    }
+}
+
+fn compressed(internal_format: GLenum, block_bytes: usize) -> PixelStorage {
+    PixelStorage {
+        format: TextureFormat::Compressed(internal_format),
+        block_bytes,
+        bgr: false,
+    }
+}
+
+pub(super) fn decode_ktx2(data: &[u8]) -> Result<ContainerImage, Texture2DError> {
+    let vk_format = read_u32(data, 12)?;
+    let width = read_u32(data, 20)?;
+    let height = read_u32(data, 24)?.max(1);
+    let depth = read_u32(data, 28)?;
+    let layers = read_u32(data, 32)?.max(1);
+    let faces = read_u32(data, 36)?;
+    let level_count = read_u32(data, 40)?.max(1);
+    let supercompression = read_u32(data, 44)?;
+
+    // supercompressed (basis, zstd) payloads and volumes aren't supported
+    if supercompression != 0 || depth > 1 || (faces != 1 && faces != 6) {
+        return Err(Texture2DError::FormatNotSupported);
+    }
+
+    let storage = match vk_format {
+        9 => uncompressed(TextureFormat::R8),
+        16 => uncompressed(TextureFormat::RG8),
+        23 => uncompressed(TextureFormat::RGB8),
+        29 => uncompressed(TextureFormat::SRGB8),
+        37 => uncompressed(TextureFormat::RGBA8),
+        43 => uncompressed(TextureFormat::SRGB8Alpha8),
+        44 => uncompressed_bgr(TextureFormat::RGBA8),
+        50 => uncompressed_bgr(TextureFormat::SRGB8Alpha8),
+        97 => uncompressed(TextureFormat::RGBA16F),
+        100 => uncompressed(TextureFormat::R32F),
+        109 => uncompressed(TextureFormat::RGBA32F),
+        131 => compressed(COMPRESSED_RGB_S3TC_DXT1_EXT, 8),
+        132 => compressed(COMPRESSED_SRGB_S3TC_DXT1_EXT, 8),
+        133 => compressed(COMPRESSED_RGBA_S3TC_DXT1_EXT, 8),
+        134 => compressed(COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT, 8),
+        135 => compressed(COMPRESSED_RGBA_S3TC_DXT3_EXT, 16),
+        136 => compressed(COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT, 16),
+        137 => compressed(COMPRESSED_RGBA_S3TC_DXT5_EXT, 16),
+        138 => compressed(COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT, 16),
+        139 => compressed(gl::COMPRESSED_RED_RGTC1, 8),
+        140 => compressed(gl::COMPRESSED_SIGNED_RED_RGTC1, 8),
+        141 => compressed(gl::COMPRESSED_RG_RGTC2, 16),
+        142 => compressed(gl::COMPRESSED_SIGNED_RG_RGTC2, 16),
+        143 => compressed(gl::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT, 16),
+        144 => compressed(gl::COMPRESSED_RGB_BPTC_SIGNED_FLOAT, 16),
+        145 => compressed(gl::COMPRESSED_RGBA_BPTC_UNORM, 16),
+        146 => compressed(gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM, 16),
+        147 => compressed(gl::COMPRESSED_RGB8_ETC2, 8),
+        148 => compressed(gl::COMPRESSED_SRGB8_ETC2, 8),
+        149 => compressed(gl::COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2, 8),
+        150 => compressed(gl::COMPRESSED_SRGB8_PUNCHTHROUGH_ALPHA1_ETC2, 8),
+        151 => compressed(gl::COMPRESSED_RGBA8_ETC2_EAC, 16),
+        152 => compressed(gl::COMPRESSED_SRGB8_ALPHA8_ETC2_EAC, 16),
+        153 => compressed(gl::COMPRESSED_R11_EAC, 8),
+        154 => compressed(gl::COMPRESSED_SIGNED_R11_EAC, 8),
+        155 => compressed(gl::COMPRESSED_RG11_EAC, 16),
+        156 => compressed(gl::COMPRESSED_SIGNED_RG11_EAC, 16),
+        _ => return Err(Texture2DError::FormatNotSupported),
+    };
+
+    // each level holds every layer and face back to back
+    let images = check_levels(width, height, layers, faces, level_count)?;
+    let mut levels = Vec::with_capacity(level_count as usize);
+    for level in 0..level_count as usize {
+        let offset = read_u64(data, 80 + level * 24)? as usize;
+        let image_size = match storage.level_size(width, height, level) {
+            Some(image_size) => image_size,
+            None => return Err(Texture2DError::DecodingError),
+        };
+
+        let mut level_images = Vec::new();
+        for image in 0..images {
+            let range = image
+                .checked_mul(image_size)
+                .and_then(|start| start.checked_add(offset))
+                .and_then(|start| Some(start..start.checked_add(image_size)?));
+            match range.and_then(|range| data.get(range)) {
+                Some(image) => level_images.push(storage.read_image(image)),
+                None => return Err(Texture2DError::DecodingError),
+            }
+        }
+        levels.push(level_images);
+    }
+
+    Ok(ContainerImage {
+        width,
+        height,
+        layers,
+        faces,
+        storage,
+        levels,
+    })
+}
+
+pub(super) fn decode_dds(data: &[u8]) -> Result<ContainerImage, Texture2DError> {
+    let height = read_u32(data, 12)?;
+    let width = read_u32(data, 16)?;
+    let level_count = read_u32(data, 28)?.max(1);
+    let pixel_flags = read_u32(data, 80)?;
+    let four_cc = match data.get(84..88) {
+        Some(four_cc) => four_cc,
+        None => return Err(Texture2DError::DecodingError),
+    };
+    let bit_count = read_u32(data, 88)?;
+    let red_mask = read_u32(data, 92)?;
+    let caps2 = read_u32(data, 112)?;
+    let faces = if caps2 & 0x200 != 0 { 6 } else { 1 };
+
+    // DDPF_FOURCC
+    let (storage, layers, mut offset): (_, _, usize) = if pixel_flags & 0x4 != 0 {
+        match four_cc {
+            b"DXT1" => (compressed(COMPRESSED_RGBA_S3TC_DXT1_EXT, 8), 1, 128),
+            b"DXT3" => (compressed(COMPRESSED_RGBA_S3TC_DXT3_EXT, 16), 1, 128),
+            b"DXT5" => (compressed(COMPRESSED_RGBA_S3TC_DXT5_EXT, 16), 1, 128),
+            b"ATI1" | b"BC4U" => (compressed(gl::COMPRESSED_RED_RGTC1, 8), 1, 128),
+            b"BC4S" => (compressed(gl::COMPRESSED_SIGNED_RED_RGTC1, 8), 1, 128),
+            b"ATI2" | b"BC5U" => (compressed(gl::COMPRESSED_RG_RGTC2, 16), 1, 128),
+            b"BC5S" => (compressed(gl::COMPRESSED_SIGNED_RG_RGTC2, 16), 1, 128),
+            b"DX10" => {
+                let storage = match read_u32(data, 128)? {
+                    2 => uncompressed(TextureFormat::RGBA32F),
+                    10 => uncompressed(TextureFormat::RGBA16F),
+                    28 => uncompressed(TextureFormat::RGBA8),
+                    29 => uncompressed(TextureFormat::SRGB8Alpha8),
+                    41 => uncompressed(TextureFormat::R32F),
+                    49 => uncompressed(TextureFormat::RG8),
+                    61 => uncompressed(TextureFormat::R8),
+                    87 => uncompressed_bgr(TextureFormat::RGBA8),
+                    91 => uncompressed_bgr(TextureFormat::SRGB8Alpha8),
+                    71 => compressed(COMPRESSED_RGBA_S3TC_DXT1_EXT, 8),
+                    72 => compressed(COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT, 8),
+                    74 => compressed(COMPRESSED_RGBA_S3TC_DXT3_EXT, 16),
+                    75 => compressed(COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT, 16),
+                    77 => compressed(COMPRESSED_RGBA_S3TC_DXT5_EXT, 16),
+                    78 => compressed(COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT, 16),
+                    80 => compressed(gl::COMPRESSED_RED_RGTC1, 8),
+                    81 => compressed(gl::COMPRESSED_SIGNED_RED_RGTC1, 8),
+                    83 => compressed(gl::COMPRESSED_RG_RGTC2, 16),
+                    84 => compressed(gl::COMPRESSED_SIGNED_RG_RGTC2, 16),
+                    95 => compressed(gl::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT, 16),
+                    96 => compressed(gl::COMPRESSED_RGB_BPTC_SIGNED_FLOAT, 16),
+                    98 => compressed(gl::COMPRESSED_RGBA_BPTC_UNORM, 16),
+                    99 => compressed(gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM, 16),
+                    _ => return Err(Texture2DError::FormatNotSupported),
+                };
+                (storage, read_u32(data, 140)?.max(1), 148)
+            }
+            _ => return Err(Texture2DError::FormatNotSupported),
+        }
+    // DDPF_RGB
+    } else if pixel_flags & 0x40 != 0 {
+        let storage = match (bit_count, red_mask) {
+            (32, 0xff) => uncompressed(TextureFormat::RGBA8),
+            (32, 0xff0000) => uncompressed_bgr(TextureFormat::RGBA8),
+            (24, 0xff) => uncompressed(TextureFormat::RGB8),
+            (24, 0xff0000) => uncompressed_bgr(TextureFormat::RGB8),
+            _ => return Err(Texture2DError::FormatNotSupported),
+        };
+        (storage, 1, 128)
+    } else {
+        return Err(Texture2DError::FormatNotSupported);
+    };
+
+    // images are stored layer by layer, each with its full mip chain
+    let images = check_levels(width, height, layers, faces, level_count)?;
+    let mut levels = vec![Vec::new(); level_count as usize];
+    for _ in 0..images {
+        for (level, level_images) in levels.iter_mut().enumerate() {
+            let end = storage
+                .level_size(width, height, level)
+                .and_then(|image_size| offset.checked_add(image_size));
+            let end = match end {
+                Some(end) => end,
+                None => return Err(Texture2DError::DecodingError),
+            };
+
+            match data.get(offset..end) {
+                Some(image) => level_images.push(storage.read_image(image)),
+                None => return Err(Texture2DError::DecodingError),
+            }
+            offset = end;
+        }
+    }
+
+    Ok(ContainerImage {
+        width,
+        height,
+        layers,
+        faces,
+        storage,
+        levels,
+    })
+}
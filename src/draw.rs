@@ -1,7 +1,10 @@
 use super::buffer::*;
+use super::framebuffer::*;
 use super::gl_buffer_resource::*;
 use super::glsl_types::*;
 use super::program::*;
+use super::ring_buffer::*;
+use super::sampler::*;
 use super::texture::*;
 use gl;
 use gl::types::*;
@@ -42,20 +45,52 @@ impl IntoUniformLocation for &str {
     }
 }
 
+pub trait IntoUniformBlock {
+    fn into(self, program: &Program) -> GLuint;
+}
+
+impl IntoUniformBlock for usize {
+    fn into(self, _program: &Program) -> GLuint {
+        self as _
+    }
+}
+
+impl IntoUniformBlock for &str {
+    fn into(self, program: &Program) -> GLuint {
+        unsafe {
+            gl::GetUniformBlockIndex(
+                program.resource.get_raw(),
+                format!("{}\0", self).as_ptr() as *const _,
+            )
+        }
+    }
+}
+
 pub struct Draw<'a> {
     // necessary
-    count: u32,
+    count: Option<u32>, // None if the count overflowed
     draw_type: GLenum,
     program: &'a Program,
-    ibo: (&'a GLBufferResource, GLenum), // resource handle, type type
+    ibo: (&'a GLBufferResource, GLenum, usize, usize, Option<usize>), // resource handle, type type, element count, byte offset, highest index
 
     // optional
-    buffers: Vec<(&'a GLBufferResource, GLenum, GLint, GLenum, GLuint)>, // resource handle, buffer type, type count, type type, attribute #
-    textures: Vec<(&'a Texture2D, GLint)>,
+    buffers: Vec<(
+        &'a GLBufferResource,
+        GLenum,
+        GLint,
+        GLenum,
+        GLuint,
+        usize,
+        usize,
+    )>, // resource handle, buffer type, type count, type type, attribute #, element count, byte offset
+    uniform_buffers: Vec<(&'a GLBufferResource, GLuint, usize, usize)>, // resource handle, block index, byte offset, byte size
+    textures: Vec<(&'a dyn TextureType, Option<&'a Sampler>, GLint)>,
     uniforms: Vec<(&'a dyn UniformType, GLint)>,
 
+    framebuffer: Option<&'a Framebuffer>,
     depth: Option<GLenum>,
     blend: Option<(GLenum, GLenum)>,
+    srgb: bool,
 }
 
 impl<'a> Draw<'a> {
@@ -63,19 +98,63 @@ impl<'a> Draw<'a> {
         count: u32,
         program: &'a Program,
         ibo: &'a Buffer<T>,
+    ) -> Self {
+        Self::start(
+            count.checked_mul(3),
+            gl::TRIANGLES,
+            program,
+            (&ibo.resource, T::get_type(), ibo.len, 0, ibo.max_index),
+        )
+    }
+
+    /// Starts a triangle draw indexed by elements pushed to a RingBuffer
+    pub fn start_tri_draw_ring<T: ElementType + 'static>(
+        count: u32,
+        program: &'a Program,
+        ring: &'a RingBuffer<T>,
+        slice: RingSlice<T>,
+    ) -> Self {
+        // stale slices and slices of other rings are treated as empty and fail the bounds check
+        let len = if ring.is_current(&slice) {
+            slice.len
+        } else {
+            0
+        };
+        Self::start(
+            count.checked_mul(3),
+            gl::TRIANGLES,
+            program,
+            (
+                &ring.resource,
+                T::get_type(),
+                len,
+                slice.offset,
+                slice.max_index,
+            ),
+        )
+    }
+
+    fn start(
+        count: Option<u32>,
+        draw_type: GLenum,
+        program: &'a Program,
+        ibo: (&'a GLBufferResource, GLenum, usize, usize, Option<usize>),
     ) -> Self {
         Self {
-            count: count * 3,
-            draw_type: gl::TRIANGLES,
+            count,
+            draw_type,
             program,
-            ibo: (&ibo.resource, T::get_type()),
+            ibo,
 
             buffers: Vec::new(),
+            uniform_buffers: Vec::new(),
             textures: Vec::new(),
             uniforms: Vec::new(),
 
+            framebuffer: None,
             depth: None,
             blend: None,
+            srgb: false,
         }
     }
 
@@ -89,18 +168,89 @@ impl<'a> Draw<'a> {
             buffer.buffer_type,
             T::get_type().0,
             T::get_type().1,
-            loc.into(&self.program) as _,
+            loc.into(self.program) as _,
+            buffer.len,
+            0,
+        ));
+        self
+    }
+
+    /// Sources a vertex attribute from elements pushed to a RingBuffer
+    pub fn with_ring_buffer<T: BufferType + 'static>(
+        mut self,
+        ring: &'a RingBuffer<T>,
+        slice: RingSlice<T>,
+        loc: impl IntoAttribLocation,
+    ) -> Self {
+        let len = if ring.is_current(&slice) {
+            slice.len
+        } else {
+            0
+        };
+        self.buffers.push((
+            &ring.resource,
+            gl::ARRAY_BUFFER,
+            T::get_type().0,
+            T::get_type().1,
+            loc.into(self.program) as _,
+            len,
+            slice.offset,
         ));
         self
     }
 
-    pub fn with_texture(mut self, texture: &'a Texture2D, loc: impl IntoUniformLocation) -> Self {
-        self.textures.push((texture, loc.into(&self.program)));
+    /// Backs a uniform block with elements pushed to a RingBuffer
+    pub fn with_ring_uniform_block<T: 'static>(
+        mut self,
+        ring: &'a RingBuffer<T>,
+        slice: RingSlice<T>,
+        block: impl IntoUniformBlock,
+    ) -> Self {
+        let len = if ring.is_current(&slice) {
+            slice.len
+        } else {
+            0
+        };
+        self.uniform_buffers.push((
+            &ring.resource,
+            block.into(self.program),
+            slice.offset,
+            len * std::mem::size_of::<T>(),
+        ));
+        self
+    }
+
+    /// Binds a texture to the sampler uniform at `loc`
+    ///
+    /// Covers every sampler type, including sampler2DShadow for a texture allocated with
+    /// `Texture2D::init_shadow_map`, whose comparison state is part of its sampler.
+    pub fn with_texture(
+        mut self,
+        texture: &'a dyn TextureType,
+        loc: impl IntoUniformLocation,
+    ) -> Self {
+        self.textures.push((texture, None, loc.into(self.program)));
+        self
+    }
+
+    pub fn with_texture_sampler(
+        mut self,
+        texture: &'a dyn TextureType,
+        sampler: &'a Sampler,
+        loc: impl IntoUniformLocation,
+    ) -> Self {
+        self.textures
+            .push((texture, Some(sampler), loc.into(self.program)));
         self
     }
 
     pub fn with_uniform(mut self, t: &'a dyn UniformType, loc: impl IntoUniformLocation) -> Self {
-        self.uniforms.push((t, loc.into(&self.program)));
+        self.uniforms.push((t, loc.into(self.program)));
+        self
+    }
+
+    pub fn to_framebuffer(mut self, framebuffer: &'a Framebuffer) -> Self {
+        self.framebuffer = Some(framebuffer);
         self
     }
 
@@ -114,10 +264,44 @@ impl<'a> Draw<'a> {
         self
     }
 
-    pub fn draw(self) {
-        // if draw count is 0, do nothing
-        if self.count == 0 {
-            return;
+    /// Encodes linear shader output to sRGB when writing to sRGB color attachments
+    pub fn enable_srgb(mut self) -> Self {
+        self.srgb = true;
+        self
+    }
+
+    /// Issues the draw call, after checking that the element buffer holds `count` elements
+    ///
+    /// Vertex attribute buffers are checked to hold the highest index written to the element
+    /// buffer. Indices written through a mapping aren't tracked, so attribute buffers of such
+    /// element buffers are only checked to be initialized and non-empty.
+    pub fn draw(self) -> Result<(), BufferError> {
+        let count = match self.count {
+            // if draw count is 0, do nothing
+            Some(0) => return Ok(()),
+            Some(count) => count,
+            None => return Err(BufferError::OutOfBounds),
+        };
+
+        // check that the buffers hold enough elements
+        if count as usize > self.ibo.2 {
+            return Err(BufferError::OutOfBounds);
+        }
+        for &(_, buffer_type, _, _, _, len, _) in self.buffers.iter() {
+            if buffer_type == 0 {
+                return Err(BufferError::BufferNotInitialized);
+            }
+            if len == 0 || self.ibo.4.is_some_and(|max_index| max_index >= len) {
+                return Err(BufferError::OutOfBounds);
+            }
+        }
+        for &(_, block, _, size) in self.uniform_buffers.iter() {
+            if block == gl::INVALID_INDEX {
+                return Err(BufferError::InvalidUniformBlock);
+            }
+            if size == 0 {
+                return Err(BufferError::OutOfBounds);
+            }
         }
 
         // unsafe time
@@ -129,8 +313,15 @@ impl<'a> Draw<'a> {
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ibo.0.get_raw());
 
             // bind other buffers
-            for (buffer_resource, buffer_type, buffer_type_count, buffer_type_type, loc) in
-                self.buffers
+            for (
+                buffer_resource,
+                buffer_type,
+                buffer_type_count,
+                buffer_type_type,
+                loc,
+                _,
+                offset,
+            ) in self.buffers
             {
                 gl::BindBuffer(buffer_type, buffer_resource.get_raw());
                 gl::EnableVertexAttribArray(loc);
@@ -146,7 +337,7 @@ impl<'a> Draw<'a> {
                             buffer_type_count,
                             buffer_type_type,
                             0,
-                            0u32 as _,
+                            offset as _,
                         );
                     }
                     _ => {
@@ -156,12 +347,26 @@ impl<'a> Draw<'a> {
                             buffer_type_type,
                             gl::FALSE,
                             0,
-                            0u32 as _,
+                            offset as _,
                         );
                     }
                 }
             }
 
+            // bind uniform buffers, one binding point per block
+            for (binding, (buffer_resource, block, offset, size)) in
+                self.uniform_buffers.into_iter().enumerate()
+            {
+                gl::UniformBlockBinding(self.program.resource.get_raw(), block, binding as _);
+                gl::BindBufferRange(
+                    gl::UNIFORM_BUFFER,
+                    binding as _,
+                    buffer_resource.get_raw(),
+                    offset as _,
+                    size as _,
+                );
+            }
+
             // uniforms
             for (uniform, loc) in self.uniforms {
                 uniform.bind_uniform(loc);
@@ -169,10 +374,16 @@ impl<'a> Draw<'a> {
 
             // attach textures
             let mut texture_target = 0;
-            for (texture, loc) in self.textures {
+            let mut cube_maps = false;
+            for (texture, sampler, loc) in self.textures {
                 gl::Uniform1i(loc, texture_target as _);
                 gl::ActiveTexture(gl::TEXTURE0 + texture_target);
-                gl::BindTexture(gl::TEXTURE_2D, texture.resource.get_raw());
+                texture.bind_texture();
+                cube_maps |= texture.is_cube_map();
+                match sampler {
+                    Some(sampler) => gl::BindSampler(texture_target, sampler.resource.get_raw()),
+                    None => gl::BindSampler(texture_target, 0),
+                }
 
                 texture_target += 1;
             }
@@ -195,8 +406,36 @@ impl<'a> Draw<'a> {
                 None => gl::Disable(gl::BLEND),
             }
 
+            // srgb
+            match self.srgb {
+                true => gl::Enable(gl::FRAMEBUFFER_SRGB),
+                false => gl::Disable(gl::FRAMEBUFFER_SRGB),
+            }
+
+            // cube maps filter across face edges
+            match cube_maps {
+                true => gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS),
+                false => gl::Disable(gl::TEXTURE_CUBE_MAP_SEAMLESS),
+            }
+
+            // framebuffer
+            let mut viewport = [0; 4];
+            if let Some(framebuffer) = self.framebuffer {
+                gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+                gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer.resource.get_raw());
+                gl::Viewport(0, 0, framebuffer.width as _, framebuffer.height as _);
+            }
+
             // draw
-            gl::DrawElements(self.draw_type, self.count as _, self.ibo.1, 0 as _);
+            gl::DrawElements(self.draw_type, count as _, self.ibo.1, self.ibo.3 as _);
+
+            // restore the default framebuffer
+            if self.framebuffer.is_some() {
+                gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+                gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+            }
         }
+
+        Ok(())
     }
 }
@@ -0,0 +1,117 @@
+use gl;
+use gl::types::*;
+
+use std::ffi::CStr;
+use std::sync::OnceLock;
+
+// Extension enums that are missing from the core profile bindings
+pub(super) const COMPRESSED_RGB_S3TC_DXT1_EXT: GLenum = 0x83F0;
+pub(super) const COMPRESSED_RGBA_S3TC_DXT1_EXT: GLenum = 0x83F1;
+pub(super) const COMPRESSED_RGBA_S3TC_DXT3_EXT: GLenum = 0x83F2;
+pub(super) const COMPRESSED_RGBA_S3TC_DXT5_EXT: GLenum = 0x83F3;
+pub(super) const COMPRESSED_SRGB_S3TC_DXT1_EXT: GLenum = 0x8C4C;
+pub(super) const COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT: GLenum = 0x8C4D;
+pub(super) const COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT: GLenum = 0x8C4E;
+pub(super) const COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT: GLenum = 0x8C4F;
+pub(super) const TEXTURE_MAX_ANISOTROPY_EXT: GLenum = 0x84FE;
+pub(super) const MAX_TEXTURE_MAX_ANISOTROPY_EXT: GLenum = 0x84FF;
+
+/// Returns the (major, minor) version of the current context
+pub(super) fn gl_version() -> (i32, i32) {
+    let mut major = 0;
+    let mut minor = 0;
+    unsafe {
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+    }
+    (major, minor)
+}
+
+// The extension list and anisotropy limit of the first context they are queried on
+static EXTENSIONS: OnceLock<Vec<String>> = OnceLock::new();
+static MAX_ANISOTROPY: OnceLock<Option<f32>> = OnceLock::new();
+
+/// Checks if the current context exposes an extension, ie. "GL_ARB_texture_compression_bptc"
+///
+/// The extensions are only queried once and assumed to be the same for every context.
+pub(super) fn has_extension(name: &str) -> bool {
+    let extensions = EXTENSIONS.get_or_init(|| unsafe {
+        let mut count = 0;
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+        (0..count as GLuint)
+            .map(|i| gl::GetStringi(gl::EXTENSIONS, i))
+            .filter(|extension| !extension.is_null())
+            .map(|extension| {
+                CStr::from_ptr(extension as _)
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect()
+    });
+    extensions.iter().any(|extension| extension == name)
+}
+
+/// Checks if immutable buffer storage, and with it persistent mapping, is available
+pub(super) fn has_buffer_storage() -> bool {
+    gl_version() >= (4, 4) || has_extension("GL_ARB_buffer_storage")
+}
+
+/// Returns the largest supported anisotropy, or None if anisotropic filtering isn't available
+pub(super) fn max_anisotropy() -> Option<f32> {
+    *MAX_ANISOTROPY.get_or_init(|| {
+        if gl_version() < (4, 6)
+            && !has_extension("GL_ARB_texture_filter_anisotropic")
+            && !has_extension("GL_EXT_texture_filter_anisotropic")
+        {
+            return None;
+        }
+
+        let mut max = 1.0;
+        unsafe {
+            gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY_EXT, &mut max);
+        }
+        Some(max)
+    })
+}
+
+/// Checks if a compressed internal format can be uploaded to the current context
+pub(super) fn is_compressed_format_supported(internal_format: GLenum) -> bool {
+    let version = gl_version();
+    match internal_format {
+        COMPRESSED_RGB_S3TC_DXT1_EXT
+        | COMPRESSED_RGBA_S3TC_DXT1_EXT
+        | COMPRESSED_RGBA_S3TC_DXT3_EXT
+        | COMPRESSED_RGBA_S3TC_DXT5_EXT => has_extension("GL_EXT_texture_compression_s3tc"),
+        COMPRESSED_SRGB_S3TC_DXT1_EXT
+        | COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT
+        | COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT
+        | COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT => {
+            has_extension("GL_EXT_texture_compression_s3tc")
+                && (has_extension("GL_EXT_texture_sRGB")
+                    || has_extension("GL_EXT_texture_compression_s3tc_srgb"))
+        }
+        gl::COMPRESSED_RED_RGTC1
+        | gl::COMPRESSED_SIGNED_RED_RGTC1
+        | gl::COMPRESSED_RG_RGTC2
+        | gl::COMPRESSED_SIGNED_RG_RGTC2 => version >= (3, 0),
+        gl::COMPRESSED_RGBA_BPTC_UNORM
+        | gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM
+        | gl::COMPRESSED_RGB_BPTC_SIGNED_FLOAT
+        | gl::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT => {
+            version >= (4, 2) || has_extension("GL_ARB_texture_compression_bptc")
+        }
+        gl::COMPRESSED_RGB8_ETC2
+        | gl::COMPRESSED_SRGB8_ETC2
+        | gl::COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2
+        | gl::COMPRESSED_SRGB8_PUNCHTHROUGH_ALPHA1_ETC2
+        | gl::COMPRESSED_RGBA8_ETC2_EAC
+        | gl::COMPRESSED_SRGB8_ALPHA8_ETC2_EAC
+        | gl::COMPRESSED_R11_EAC
+        | gl::COMPRESSED_SIGNED_R11_EAC
+        | gl::COMPRESSED_RG11_EAC
+        | gl::COMPRESSED_SIGNED_RG11_EAC => {
+            version >= (4, 3) || has_extension("GL_ARB_ES3_compatibility")
+        }
+        _ => false,
+    }
+}
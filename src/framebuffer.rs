@@ -0,0 +1,478 @@
+use gl;
+use gl::types::*;
+
+use std::path::*;
+use std::sync::Arc;
+
+use super::gl_framebuffer_resource::*;
+use super::gl_texture_resource::*;
+use super::pixel_store::*;
+use super::renderbuffer::*;
+use super::texture::*;
+use super::texture_multisample::*;
+
+#[derive(Debug)]
+pub enum FramebufferError {
+    InvalidAttachmentFormat,
+    InvalidAttachmentIndex,
+    FormatNotSupported,
+    OutOfBounds,
+    CouldNotWriteFile,
+    MismatchedDimensions,
+    Undefined,
+    IncompleteAttachment,
+    IncompleteMissingAttachment,
+    IncompleteDrawBuffer,
+    IncompleteReadBuffer,
+    IncompleteMultisample,
+    IncompleteLayerTargets,
+    Unsupported,
+    Unknown(GLenum),
+}
+
+/// A framebuffer object rendering to textures and renderbuffers
+///
+/// Attached textures can't replace their storage until the framebuffer is dropped or the
+/// attachment point is attached to something else.
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    draw_buffers: Vec<GLenum>,
+    // keeps attached textures from replacing their texture objects
+    attachments: Vec<(GLenum, Arc<()>)>,
+    pub(super) resource: GLFramebufferResource,
+}
+
+impl Framebuffer {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            draw_buffers: Vec::new(),
+            attachments: Vec::new(),
+            resource: GLFramebufferResource::new(),
+        }
+    }
+
+    /// Attaches a Texture2D to the color attachment point `index`
+    pub fn attach_color(
+        &mut self,
+        index: u32,
+        texture: &Texture2D,
+    ) -> Result<(), FramebufferError> {
+        // check for validity
+        if !texture.format.is_color() {
+            return Err(FramebufferError::InvalidAttachmentFormat);
+        }
+        self.check_dimensions(texture.width, texture.height)?;
+        let attachment = self.add_draw_buffer(index)?;
+
+        // attach
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.resource.get_raw());
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                attachment,
+                gl::TEXTURE_2D,
+                texture.resource.get_raw(),
+                0,
+            );
+            gl::DrawBuffers(self.draw_buffers.len() as _, self.draw_buffers.as_ptr());
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        self.track_attachment(attachment, Some(&texture.resource));
+
+        Ok(())
+    }
+
+    /// Attaches a color Renderbuffer to the color attachment point `index`
+    pub fn attach_color_renderbuffer(
+        &mut self,
+        index: u32,
+        renderbuffer: &Renderbuffer,
+    ) -> Result<(), FramebufferError> {
+        // check for validity
+        if !renderbuffer.format.is_color() {
+            return Err(FramebufferError::InvalidAttachmentFormat);
+        }
+        self.check_dimensions(renderbuffer.width, renderbuffer.height)?;
+        let attachment = self.add_draw_buffer(index)?;
+
+        // attach
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.resource.get_raw());
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                attachment,
+                gl::RENDERBUFFER,
+                renderbuffer.resource.get_raw(),
+            );
+            gl::DrawBuffers(self.draw_buffers.len() as _, self.draw_buffers.as_ptr());
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        self.track_attachment(attachment, None);
+
+        Ok(())
+    }
+
+    /// Attaches a Texture2DMultisample to the color attachment point `index`
+    pub fn attach_color_multisample(
+        &mut self,
+        index: u32,
+        texture: &Texture2DMultisample,
+    ) -> Result<(), FramebufferError> {
+        // check for validity
+        if !texture.format.is_color() {
+            return Err(FramebufferError::InvalidAttachmentFormat);
+        }
+        self.check_dimensions(texture.width, texture.height)?;
+        let attachment = self.add_draw_buffer(index)?;
+
+        // attach
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.resource.get_raw());
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                attachment,
+                gl::TEXTURE_2D_MULTISAMPLE,
+                texture.resource.get_raw(),
+                0,
+            );
+            gl::DrawBuffers(self.draw_buffers.len() as _, self.draw_buffers.as_ptr());
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        self.track_attachment(attachment, Some(&texture.resource));
+
+        Ok(())
+    }
+
+    /// Attaches a depth or stencil Texture2D to the matching attachment point
+    pub fn attach_depth(&mut self, texture: &Texture2D) -> Result<(), FramebufferError> {
+        // check for validity
+        let attachment = match texture.format.depth_stencil_attachment() {
+            Some(attachment) => attachment,
+            None => return Err(FramebufferError::InvalidAttachmentFormat),
+        };
+        self.check_dimensions(texture.width, texture.height)?;
+
+        // attach
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.resource.get_raw());
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                attachment,
+                gl::TEXTURE_2D,
+                texture.resource.get_raw(),
+                0,
+            );
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        self.track_attachment(attachment, Some(&texture.resource));
+
+        Ok(())
+    }
+
+    /// Attaches a depth or stencil Texture2DMultisample to the matching attachment point
+    pub fn attach_depth_multisample(
+        &mut self,
+        texture: &Texture2DMultisample,
+    ) -> Result<(), FramebufferError> {
+        // check for validity
+        let attachment = match texture.format.depth_stencil_attachment() {
+            Some(attachment) => attachment,
+            None => return Err(FramebufferError::InvalidAttachmentFormat),
+        };
+        self.check_dimensions(texture.width, texture.height)?;
+
+        // attach
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.resource.get_raw());
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                attachment,
+                gl::TEXTURE_2D_MULTISAMPLE,
+                texture.resource.get_raw(),
+                0,
+            );
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        self.track_attachment(attachment, Some(&texture.resource));
+
+        Ok(())
+    }
+
+    /// Attaches a depth or stencil Renderbuffer to the matching attachment point
+    pub fn attach_depth_renderbuffer(
+        &mut self,
+        renderbuffer: &Renderbuffer,
+    ) -> Result<(), FramebufferError> {
+        // check for validity
+        let attachment = match renderbuffer.format.depth_stencil_attachment() {
+            Some(attachment) => attachment,
+            None => return Err(FramebufferError::InvalidAttachmentFormat),
+        };
+        self.check_dimensions(renderbuffer.width, renderbuffer.height)?;
+
+        // attach
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.resource.get_raw());
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                attachment,
+                gl::RENDERBUFFER,
+                renderbuffer.resource.get_raw(),
+            );
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        self.track_attachment(attachment, None);
+
+        Ok(())
+    }
+
+    /// Checks if the framebuffer is complete and can be rendered to
+    pub fn check(&self) -> Result<(), FramebufferError> {
+        let status = unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.resource.get_raw());
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            status
+        };
+
+        match status {
+            gl::FRAMEBUFFER_COMPLETE => Ok(()),
+            gl::FRAMEBUFFER_UNDEFINED => Err(FramebufferError::Undefined),
+            gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => Err(FramebufferError::IncompleteAttachment),
+            gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => {
+                Err(FramebufferError::IncompleteMissingAttachment)
+            }
+            gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => Err(FramebufferError::IncompleteDrawBuffer),
+            gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => Err(FramebufferError::IncompleteReadBuffer),
+            gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => Err(FramebufferError::IncompleteMultisample),
+            gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => {
+                Err(FramebufferError::IncompleteLayerTargets)
+            }
+            gl::FRAMEBUFFER_UNSUPPORTED => Err(FramebufferError::Unsupported),
+            status => Err(FramebufferError::Unknown(status)),
+        }
+    }
+
+    /// Clears the color and depth attachments of the framebuffer
+    pub fn clear(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.resource.get_raw());
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    /// Resolves a multisampled framebuffer into `other`, copying every color attachment to the
+    /// attachment with the same index, along with the depth and stencil attachments
+    ///
+    /// Both framebuffers must have the same dimensions. Attachments missing from either
+    /// framebuffer are skipped.
+    pub fn resolve_into(&self, other: &Framebuffer) -> Result<(), FramebufferError> {
+        if self.width != other.width || self.height != other.height {
+            return Err(FramebufferError::MismatchedDimensions);
+        }
+
+        let (width, height) = (self.width as GLint, self.height as GLint);
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.resource.get_raw());
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, other.resource.get_raw());
+
+            // color attachments are blitted one at a time
+            for &attachment in &self.draw_buffers {
+                if !other.draw_buffers.contains(&attachment) {
+                    continue;
+                }
+                gl::ReadBuffer(attachment);
+                gl::DrawBuffers(1, &attachment);
+                gl::BlitFramebuffer(
+                    0,
+                    0,
+                    width,
+                    height,
+                    0,
+                    0,
+                    width,
+                    height,
+                    gl::COLOR_BUFFER_BIT,
+                    gl::NEAREST,
+                );
+            }
+
+            // depth and stencil are ignored if either framebuffer lacks them
+            gl::BlitFramebuffer(
+                0,
+                0,
+                width,
+                height,
+                0,
+                0,
+                width,
+                height,
+                gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT,
+                gl::NEAREST,
+            );
+
+            // restore the draw buffers of the target
+            gl::DrawBuffers(other.draw_buffers.len() as _, other.draw_buffers.as_ptr());
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+        }
+
+        Ok(())
+    }
+
+    /// Reads a region of the color attachment `index` back into memory as tightly packed RGBA
+    pub fn read_pixels(
+        &self,
+        index: u32,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        flip: bool,
+    ) -> Result<Vec<u8>, FramebufferError> {
+        // check if we're reading out of bounds
+        if x + width > self.width || y + height > self.height {
+            return Err(FramebufferError::OutOfBounds);
+        }
+        if !self.draw_buffers.contains(&(gl::COLOR_ATTACHMENT0 + index)) {
+            return Err(FramebufferError::InvalidAttachmentIndex);
+        }
+
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.resource.get_raw());
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0 + index);
+            let data = read_bound_pixels(x, y, width, height, gl::RGBA, flip);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+            data
+        }
+    }
+
+    /// Saves the contents of the color attachment `index` to a RGBA png file
+    ///
+    /// Png files store rows top to bottom while gl renders row 0 at the bottom, so `flip` is
+    /// usually true. This matches Texture2D::save_png on the attached texture.
+    pub fn save_png(&self, index: u32, path: &Path, flip: bool) -> Result<(), FramebufferError> {
+        let data = self.read_pixels(index, 0, 0, self.width, self.height, flip)?;
+        match write_png(path, self.width, self.height, gl::RGBA, &data) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(FramebufferError::CouldNotWriteFile),
+        }
+    }
+
+    // every attachment must share the dimensions of the first one
+    fn check_dimensions(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        if self.width == 0 && self.height == 0 {
+            self.width = width;
+            self.height = height;
+        }
+
+        if width != self.width || height != self.height {
+            return Err(FramebufferError::MismatchedDimensions);
+        }
+
+        Ok(())
+    }
+
+    // records the texture attached to an attachment point, releasing the ones it replaces
+    fn track_attachment(&mut self, attachment: GLenum, texture: Option<&GLTextureResource>) {
+        // a depth stencil attachment replaces both the depth and the stencil attachment
+        self.attachments
+            .retain(|&(other, _)| match (attachment, other) {
+                (gl::DEPTH_STENCIL_ATTACHMENT, gl::DEPTH_ATTACHMENT)
+                | (gl::DEPTH_STENCIL_ATTACHMENT, gl::STENCIL_ATTACHMENT) => false,
+                _ => other != attachment,
+            });
+        if let Some(texture) = texture {
+            self.attachments.push((attachment, texture.attachment()));
+        }
+    }
+
+    // validates a color attachment index and records it as a draw buffer
+    fn add_draw_buffer(&mut self, index: u32) -> Result<GLenum, FramebufferError> {
+        let mut max_attachments = 0;
+        unsafe {
+            gl::GetIntegerv(gl::MAX_COLOR_ATTACHMENTS, &mut max_attachments);
+        }
+        if index >= max_attachments as u32 {
+            return Err(FramebufferError::InvalidAttachmentIndex);
+        }
+
+        let attachment = gl::COLOR_ATTACHMENT0 + index;
+        if !self.draw_buffers.contains(&attachment) {
+            self.draw_buffers.push(attachment);
+            self.draw_buffers.sort();
+        }
+
+        Ok(attachment)
+    }
+}
+
+/// Saves the visible region of a color buffer of the default framebuffer to a RGB png file
+///
+/// `buffer` is usually gl::BACK before swapping buffers, the back buffer is undefined after a
+/// swap. Single buffered contexts only have gl::FRONT.
+pub fn save_screenshot(path: &Path, buffer: GLenum) -> Result<(), FramebufferError> {
+    let mut viewport = [0; 4];
+    let data = unsafe {
+        gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        gl::ReadBuffer(buffer);
+        read_bound_pixels(
+            viewport[0] as _,
+            viewport[1] as _,
+            viewport[2] as _,
+            viewport[3] as _,
+            gl::RGB,
+            true,
+        )?
+    };
+
+    match write_png(path, viewport[2] as _, viewport[3] as _, gl::RGB, &data) {
+        Ok(()) => Ok(()),
+        Err(_) => Err(FramebufferError::CouldNotWriteFile),
+    }
+}
+
+// reads a region of the currently bound read framebuffer
+pub(super) unsafe fn read_bound_pixels(
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    format: GLenum,
+    flip: bool,
+) -> Result<Vec<u8>, FramebufferError> {
+    // get the number of bytes per color
+    let bytes_per_color = match format {
+        gl::RGB => 3,
+        gl::RGBA => 4,
+        _ => return Err(FramebufferError::FormatNotSupported),
+    };
+
+    // read
+    let row_len = (bytes_per_color * width) as usize;
+    let mut data = vec![0u8; row_len * height as usize];
+    let pack = PixelStore::pack();
+    gl::ReadPixels(
+        x as _,
+        y as _,
+        width as _,
+        height as _,
+        format,
+        gl::UNSIGNED_BYTE,
+        data.as_mut_ptr() as _,
+    );
+    drop(pack);
+
+    // gl stores rows bottom to top
+    if flip {
+        flip_rows(&mut data, row_len);
+    }
+
+    Ok(data)
+}
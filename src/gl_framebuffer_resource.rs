@@ -0,0 +1,32 @@
+use gl;
+use gl::types::*;
+
+// Wraps an opengl framebuffer resource, providing a destructor
+pub(super) struct GLFramebufferResource {
+    handle: GLuint,
+}
+
+impl GLFramebufferResource {
+    pub(super) fn new() -> Self {
+        // create the handle
+        let mut handle = 0;
+        unsafe {
+            gl::GenFramebuffers(1, &mut handle as _);
+        }
+
+        // create the object
+        Self { handle }
+    }
+
+    pub(super) unsafe fn get_raw(&self) -> GLuint {
+        self.handle
+    }
+}
+
+impl Drop for GLFramebufferResource {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &mut self.handle as _);
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use gl;
+use gl::types::*;
+
+// Wraps an opengl renderbuffer resource, providing a destructor
+pub(super) struct GLRenderbufferResource {
+    handle: GLuint,
+}
+
+impl GLRenderbufferResource {
+    pub(super) fn new() -> Self {
+        // create the handle
+        let mut handle = 0;
+        unsafe {
+            gl::GenRenderbuffers(1, &mut handle as _);
+        }
+
+        // create the object
+        Self { handle }
+    }
+
+    pub(super) unsafe fn get_raw(&self) -> GLuint {
+        self.handle
+    }
+}
+
+impl Drop for GLRenderbufferResource {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteRenderbuffers(1, &mut self.handle as _);
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use gl;
+use gl::types::*;
+
+// Wraps an opengl sampler resource, providing a destructor
+pub(super) struct GLSamplerResource {
+    handle: GLuint,
+}
+
+impl GLSamplerResource {
+    pub(super) fn new() -> Self {
+        // create the handle
+        let mut handle = 0;
+        unsafe {
+            gl::GenSamplers(1, &mut handle as _);
+        }
+
+        // create the object
+        Self { handle }
+    }
+
+    pub(super) unsafe fn get_raw(&self) -> GLuint {
+        self.handle
+    }
+}
+
+impl Drop for GLSamplerResource {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteSamplers(1, &mut self.handle as _);
+        }
+    }
+}
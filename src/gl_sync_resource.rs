@@ -0,0 +1,55 @@
+use gl;
+use gl::types::*;
+
+// The outcome of waiting on a fence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum SyncStatus {
+    Signaled,
+    TimedOut,
+    Failed,
+}
+
+// Wraps an opengl fence sync object, providing a destructor
+pub(super) struct GLSyncResource {
+    handle: GLsync,
+}
+
+impl GLSyncResource {
+    // Inserts a fence after every command issued so far
+    pub(super) fn new() -> Self {
+        // create the handle
+        let handle = unsafe { gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) };
+
+        // create the object
+        Self { handle }
+    }
+
+    // Checks if the fence has been reached, blocking for at most `timeout` nanoseconds
+    pub(super) fn wait(&self, timeout: u64) -> SyncStatus {
+        let status =
+            unsafe { gl::ClientWaitSync(self.handle, gl::SYNC_FLUSH_COMMANDS_BIT, timeout) };
+        match status {
+            gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED => SyncStatus::Signaled,
+            gl::TIMEOUT_EXPIRED => SyncStatus::TimedOut,
+            _ => SyncStatus::Failed,
+        }
+    }
+
+    // Blocks until the fence has been reached or waiting on it fails
+    pub(super) fn wait_forever(&self) -> SyncStatus {
+        loop {
+            match self.wait(1_000_000) {
+                SyncStatus::TimedOut => continue,
+                status => return status,
+            }
+        }
+    }
+}
+
+impl Drop for GLSyncResource {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteSync(self.handle);
+        }
+    }
+}
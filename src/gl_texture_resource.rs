@@ -1,10 +1,14 @@
 use gl;
 use gl::types::*;
 
+use std::sync::Arc;
+
 /// Wraps an opengl texture resource, providing a destructor
 #[derive(Debug)]
 pub struct GLTextureResource {
     handle: GLuint,
+    // cloned by every Framebuffer the texture is attached to
+    attachments: Arc<()>,
 }
 
 impl GLTextureResource {
@@ -17,12 +21,25 @@ impl GLTextureResource {
         }
 
         // create the object
-        Self { handle }
+        Self {
+            handle,
+            attachments: Arc::new(()),
+        }
     }
 
     pub unsafe fn get_raw(&self) -> GLuint {
         self.handle
     }
+
+    // a token held by a Framebuffer for as long as the texture is attached to it
+    pub(super) fn attachment(&self) -> Arc<()> {
+        self.attachments.clone()
+    }
+
+    // whether a Framebuffer holds the texture as an attachment
+    pub(super) fn is_attached(&self) -> bool {
+        Arc::strong_count(&self.attachments) > 1
+    }
 }
 
 impl Drop for GLTextureResource {
@@ -0,0 +1,166 @@
+use gl;
+use gl::types::*;
+
+use std::mem;
+
+use super::buffer::*;
+
+/// A Vec mirrored into a Buffer, uploading only the elements modified since the last `sync`
+///
+/// Modifications only touch the CPU copy until `sync` is called. When the elements outgrow the
+/// buffer, it's reallocated at double the size and the synced elements are copied over on the
+/// GPU.
+pub struct GpuVec<T> {
+    buffer: Buffer<T>,
+    data: Vec<T>,
+    dirty: Vec<(usize, usize)>, // start, end, sorted and not touching
+}
+
+impl<T: Copy> GpuVec<T> {
+    pub fn new(buffer_type: GLenum, usage: BufferUsage) -> Self {
+        Self::with_capacity(buffer_type, 0, usage)
+    }
+
+    /// Creates an empty vector with storage for `capacity` elements on both the CPU and GPU
+    pub fn with_capacity(buffer_type: GLenum, capacity: usize, usage: BufferUsage) -> Self {
+        let mut buffer = Buffer::new();
+        let _ = buffer.init_null(buffer_type, capacity, usage);
+
+        Self {
+            buffer,
+            data: Vec::with_capacity(capacity),
+            dirty: Vec::new(),
+        }
+    }
+
+    /// The buffer, which only holds the elements as of the last `sync`
+    pub fn buffer(&self) -> &Buffer<T> {
+        &self.buffer
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.data.push(value);
+        let len = self.data.len();
+        self.mark_dirty(len - 1, len);
+    }
+
+    pub fn extend_from_slice(&mut self, values: &[T]) {
+        let start = self.data.len();
+        self.data.extend_from_slice(values);
+        let end = self.data.len();
+        self.mark_dirty(start, end);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let value = self.data.pop();
+        self.truncate(self.data.len());
+        value
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.data.truncate(len);
+        let len = self.data.len();
+        self.dirty.retain(|&(start, _)| start < len);
+        if let Some(range) = self.dirty.last_mut() {
+            range.1 = range.1.min(len);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Replaces the element at `index`, returning false if it's out of bounds
+    pub fn set(&mut self, index: usize, value: T) -> bool {
+        match self.data.get_mut(index) {
+            Some(element) => *element = value,
+            None => return false,
+        }
+        self.mark_dirty(index, index + 1);
+        true
+    }
+
+    /// Gives mutable access to the elements in `start..end`, marking all of them as modified
+    pub fn slice_mut(&mut self, start: usize, end: usize) -> Option<&mut [T]> {
+        if start > end || end > self.data.len() {
+            return None;
+        }
+        self.mark_dirty(start, end);
+        Some(&mut self.data[start..end])
+    }
+
+    /// Uploads the modified elements, growing the buffer if needed
+    pub fn sync(&mut self) -> Result<(), BufferError> {
+        let len = self.data.len();
+        if len > self.buffer.capacity {
+            self.grow(len)?;
+        }
+
+        for &(start, end) in self.dirty.iter() {
+            self.buffer.splice(start, &self.data[start..end])?;
+        }
+        self.dirty.clear();
+        self.buffer.len = len;
+
+        Ok(())
+    }
+
+    // reallocates the buffer with room for at least `len` elements, keeping the synced ones
+    fn grow(&mut self, len: usize) -> Result<(), BufferError> {
+        let capacity = len.max(self.buffer.capacity * 2).max(4);
+        let mut buffer = Buffer::new();
+        buffer.init_null(self.buffer.buffer_type, capacity, self.buffer.usage)?;
+
+        // copy the elements that are still valid, everything past them is uploaded anyway
+        let synced = self.buffer.len.min(len);
+        if synced > 0 {
+            unsafe {
+                gl::BindBuffer(gl::COPY_READ_BUFFER, self.buffer.resource.get_raw());
+                gl::BindBuffer(gl::COPY_WRITE_BUFFER, buffer.resource.get_raw());
+                gl::CopyBufferSubData(
+                    gl::COPY_READ_BUFFER,
+                    gl::COPY_WRITE_BUFFER,
+                    0,
+                    0,
+                    (synced * mem::size_of::<T>()) as _,
+                );
+            }
+        }
+        buffer.len = synced;
+        buffer.max_index = self.buffer.max_index;
+        self.buffer = buffer;
+
+        Ok(())
+    }
+
+    // adds a range to the dirty list, merging it with the ranges it touches
+    fn mark_dirty(&mut self, start: usize, end: usize) {
+        if start >= end {
+            return;
+        }
+
+        // find the ranges touching the new one
+        let first = self.dirty.iter().position(|&(_, e)| e >= start);
+        let first = first.unwrap_or(self.dirty.len());
+        let last = self.dirty.iter().rposition(|&(s, _)| s <= end);
+        let last = last.map_or(first, |last| last + 1).max(first);
+
+        let mut range = (start, end);
+        for &(s, e) in self.dirty[first..last].iter() {
+            range = (range.0.min(s), range.1.max(e));
+        }
+        self.dirty.splice(first..last, Some(range));
+    }
+}
@@ -0,0 +1,623 @@
+#[cfg(feature = "jpeg")]
+use jpeg_decoder;
+use png;
+use png::HasParameters;
+use std::ffi::OsStr;
+use std::path::*;
+use std::str;
+
+use super::texture::*;
+use super::texture_format::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum ImageFormat {
+    Png,
+    Jpeg,
+    Bmp,
+    Tga,
+    Hdr,
+    Ktx2,
+    Dds,
+}
+
+/// A decoded image, stored top row first
+pub(super) struct DecodedImage {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    pub data: Vec<u8>,
+}
+
+/// Detects the format of an image by its magic bytes, falling back to the extension for formats without one
+pub(super) fn detect_format(path: &Path, data: &[u8]) -> Option<ImageFormat> {
+    if data.starts_with(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n']) {
+        return Some(ImageFormat::Png);
+    }
+    if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        return Some(ImageFormat::Jpeg);
+    }
+    if data.starts_with(b"BM") {
+        return Some(ImageFormat::Bmp);
+    }
+    if data.starts_with(&[
+        0xab, b'K', b'T', b'X', b' ', b'2', b'0', 0xbb, b'\r', b'\n', 0x1a, b'\n',
+    ]) {
+        return Some(ImageFormat::Ktx2);
+    }
+    if data.starts_with(b"DDS ") {
+        return Some(ImageFormat::Dds);
+    }
+    if data.starts_with(b"#?RADIANCE") || data.starts_with(b"#?RGBE") {
+        return Some(ImageFormat::Hdr);
+    }
+    if data.ends_with(b"TRUEVISION-XFILE.\0") {
+        return Some(ImageFormat::Tga);
+    }
+
+    // original tga files have no signature at all
+    match path.extension().and_then(OsStr::to_str) {
+        Some("tga") | Some("TGA") => Some(ImageFormat::Tga),
+        _ => None,
+    }
+}
+
+pub(super) fn decode(
+    format: ImageFormat,
+    data: &[u8],
+    options: LoadOptions,
+) -> Result<DecodedImage, Texture2DError> {
+    let mut image = match format {
+        ImageFormat::Png => decode_png(data, options),
+        #[cfg(feature = "jpeg")]
+        ImageFormat::Jpeg => decode_jpeg(data),
+        #[cfg(feature = "bmp")]
+        ImageFormat::Bmp => decode_bmp(data),
+        #[cfg(feature = "tga")]
+        ImageFormat::Tga => decode_tga(data),
+        #[cfg(feature = "hdr")]
+        ImageFormat::Hdr => decode_hdr(data, options),
+        _ => Err(Texture2DError::FormatNotSupported),
+    }?;
+
+    // 8 bit color images are assumed to be sRGB encoded
+    let color_space = match (options.color_space, format) {
+        (Some(color_space), _) => color_space,
+        (None, ImageFormat::Png) => png_color_space(data),
+        (None, _) => ColorSpace::Srgb,
+    };
+    image.format = image.format.with_color_space(color_space);
+
+    Ok(image)
+}
+
+/// Returns the color space declared by the sRGB or gAMA chunk of a png, defaulting to sRGB
+fn png_color_space(data: &[u8]) -> ColorSpace {
+    // walk the chunks between the signature and the image data
+    let mut color_space = ColorSpace::Srgb;
+    let mut offset = 8;
+    while offset + 8 <= data.len() {
+        let len = u32::from_be_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ]) as usize;
+        let chunk_type = &data[offset + 4..offset + 8];
+        let chunk = data.get(offset + 8..offset + 8 + len);
+        match (chunk_type, chunk) {
+            // the sRGB chunk takes precedence over gAMA, so stop here
+            (b"sRGB", _) => return ColorSpace::Srgb,
+            (b"gAMA", Some(chunk)) if len == 4 => {
+                // gamma is stored as 100000 / gamma, a value of 1.0 means linear data
+                let gamma = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+                if (95000..=105000).contains(&gamma) {
+                    color_space = ColorSpace::Linear;
+                }
+            }
+            (b"IDAT", _) | (b"IEND", _) => break,
+            _ => {}
+        }
+        // skip the chunk's length, type, data and crc
+        offset += 12 + len;
+    }
+
+    color_space
+}
+
+fn decode_png(data: &[u8], options: LoadOptions) -> Result<DecodedImage, Texture2DError> {
+    // palettes and low bit depths are always expanded to 8 bits per channel
+    let mut png_decoder = png::Decoder::new(data);
+    if options.strip_16 {
+        png_decoder.set(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    } else {
+        png_decoder.set(png::Transformations::EXPAND);
+    }
+    let (info, mut reader) = match png_decoder.read_info() {
+        Ok((info, reader)) => (info, reader),
+        Err(_) => return Err(Texture2DError::FormatNotSupported),
+    };
+
+    let mut buf = vec![0; info.buffer_size()];
+    if reader.next_frame(&mut buf).is_err() {
+        return Err(Texture2DError::DecodingError);
+    }
+
+    // the output info reports 8 bits for expanded images, so check the source depth
+    let is_16_bit = !options.strip_16 && reader.info().bit_depth == png::BitDepth::Sixteen;
+
+    let (format_8, format_16) = match info.color_type {
+        png::ColorType::Grayscale => (TextureFormat::R8, TextureFormat::R16),
+        png::ColorType::GrayscaleAlpha => (TextureFormat::RG8, TextureFormat::RG16),
+        png::ColorType::RGB => (TextureFormat::RGB8, TextureFormat::RGB16),
+        png::ColorType::RGBA => (TextureFormat::RGBA8, TextureFormat::RGBA16),
+        png::ColorType::Indexed => return Err(Texture2DError::FormatNotSupported),
+    };
+
+    if is_16_bit {
+        // png stores 16 bit samples big endian
+        if cfg!(target_endian = "little") {
+            buf.chunks_mut(2).for_each(|sample| sample.swap(0, 1));
+        }
+        Ok(DecodedImage {
+            width: info.width,
+            height: info.height,
+            format: format_16,
+            data: buf,
+        })
+    } else {
+        Ok(DecodedImage {
+            width: info.width,
+            height: info.height,
+            format: format_8,
+            data: buf,
+        })
+    }
+}
+
+#[cfg(feature = "jpeg")]
+fn decode_jpeg(data: &[u8]) -> Result<DecodedImage, Texture2DError> {
+    let mut decoder = jpeg_decoder::Decoder::new(data);
+    let pixels = match decoder.decode() {
+        Ok(pixels) => pixels,
+        Err(_) => return Err(Texture2DError::DecodingError),
+    };
+    let info = match decoder.info() {
+        Some(info) => info,
+        None => return Err(Texture2DError::DecodingError),
+    };
+
+    let (format, data) = match info.pixel_format {
+        jpeg_decoder::PixelFormat::L8 => (TextureFormat::R8, pixels),
+        jpeg_decoder::PixelFormat::RGB24 => (TextureFormat::RGB8, pixels),
+        jpeg_decoder::PixelFormat::CMYK32 => {
+            let rgb = pixels
+                .chunks(4)
+                .flat_map(|cmyk| {
+                    let k = 255 - cmyk[3] as u32;
+                    vec![
+                        ((255 - cmyk[0] as u32) * k / 255) as u8,
+                        ((255 - cmyk[1] as u32) * k / 255) as u8,
+                        ((255 - cmyk[2] as u32) * k / 255) as u8,
+                    ]
+                })
+                .collect();
+            (TextureFormat::RGB8, rgb)
+        }
+        jpeg_decoder::PixelFormat::L16 => return Err(Texture2DError::FormatNotSupported),
+    };
+
+    Ok(DecodedImage {
+        width: info.width as _,
+        height: info.height as _,
+        format,
+        data,
+    })
+}
+
+#[cfg(any(feature = "bmp", feature = "tga"))]
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, Texture2DError> {
+    match data.get(offset..offset + 2) {
+        Some(bytes) => Ok(bytes[0] as u16 | (bytes[1] as u16) << 8),
+        None => Err(Texture2DError::DecodingError),
+    }
+}
+
+#[cfg(feature = "bmp")]
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, Texture2DError> {
+    match data.get(offset..offset + 4) {
+        Some(bytes) => Ok(bytes[0] as u32
+            | (bytes[1] as u32) << 8
+            | (bytes[2] as u32) << 16
+            | (bytes[3] as u32) << 24),
+        None => Err(Texture2DError::DecodingError),
+    }
+}
+
+#[cfg(feature = "bmp")]
+fn decode_bmp(data: &[u8]) -> Result<DecodedImage, Texture2DError> {
+    // file header
+    let pixel_offset = read_u32(data, 10)? as usize;
+
+    // info header, only BITMAPINFOHEADER and its successors are supported
+    let header_size = read_u32(data, 14)? as usize;
+    if header_size < 40 {
+        return Err(Texture2DError::FormatNotSupported);
+    }
+    let width = read_u32(data, 18)? as i32;
+    let height = read_u32(data, 22)? as i32;
+    let bits_per_pixel = read_u16(data, 28)?;
+    let compression = read_u32(data, 30)?;
+    let palette_len = read_u32(data, 46)? as usize;
+
+    // BI_RGB, or BI_BITFIELDS with the usual masks
+    match (compression, bits_per_pixel) {
+        (0, _) | (3, 32) => {}
+        _ => return Err(Texture2DError::FormatNotSupported),
+    }
+    if width <= 0 || height == 0 {
+        return Err(Texture2DError::DecodingError);
+    }
+
+    // rows are stored bottom to top unless the height is negative
+    let bottom_up = height > 0;
+    let width = width as usize;
+    let height = height.unsigned_abs() as usize;
+    let row_len = (bits_per_pixel as usize * width).div_ceil(32) * 4;
+    let palette_offset = 14 + header_size;
+
+    let (format, bytes_per_color) = match bits_per_pixel {
+        8 | 24 => (TextureFormat::RGB8, 3),
+        32 => (TextureFormat::RGBA8, 4),
+        _ => return Err(Texture2DError::FormatNotSupported),
+    };
+
+    // the alpha channel is only meaningful when a bitfields header has an alpha mask, it's
+    // usually left as 0 otherwise
+    let has_alpha = compression == 3 && header_size >= 56 && read_u32(data, 14 + 52)? != 0;
+
+    // check that the file holds every row before allocating
+    let pixels_end = row_len
+        .checked_mul(height)
+        .and_then(|size| size.checked_add(pixel_offset));
+    match pixels_end {
+        Some(end) if end <= data.len() => {}
+        _ => return Err(Texture2DError::DecodingError),
+    }
+
+    let mut pixels = Vec::with_capacity(width * height * bytes_per_color);
+    for row in 0..height {
+        let row = if bottom_up { height - row - 1 } else { row };
+        let start = pixel_offset + row * row_len;
+        let row_data = match data.get(start..start + row_len) {
+            Some(row_data) => row_data,
+            None => return Err(Texture2DError::DecodingError),
+        };
+
+        for x in 0..width {
+            match bits_per_pixel {
+                8 => {
+                    let index = row_data[x] as usize;
+                    if palette_len != 0 && index >= palette_len {
+                        return Err(Texture2DError::DecodingError);
+                    }
+                    let entry = palette_offset + index * 4;
+                    match data.get(entry..entry + 3) {
+                        Some(bgr) => pixels.extend_from_slice(&[bgr[2], bgr[1], bgr[0]]),
+                        None => return Err(Texture2DError::DecodingError),
+                    }
+                }
+                24 => {
+                    let bgr = &row_data[x * 3..x * 3 + 3];
+                    pixels.extend_from_slice(&[bgr[2], bgr[1], bgr[0]]);
+                }
+                _ => {
+                    let bgra = &row_data[x * 4..x * 4 + 4];
+                    let alpha = if has_alpha { bgra[3] } else { 255 };
+                    pixels.extend_from_slice(&[bgra[2], bgra[1], bgra[0], alpha]);
+                }
+            }
+        }
+    }
+
+    Ok(DecodedImage {
+        width: width as _,
+        height: height as _,
+        format,
+        data: pixels,
+    })
+}
+
+#[cfg(feature = "tga")]
+fn decode_tga(data: &[u8]) -> Result<DecodedImage, Texture2DError> {
+    if data.len() < 18 {
+        return Err(Texture2DError::DecodingError);
+    }
+    let id_len = data[0] as usize;
+    let color_map_type = data[1];
+    let image_type = data[2];
+    let color_map_len = read_u16(data, 5)? as usize;
+    let color_map_depth = data[7] as usize;
+    let width = read_u16(data, 12)? as usize;
+    let height = read_u16(data, 14)? as usize;
+    let bits_per_pixel = data[16];
+    let descriptor = data[17];
+
+    // only uncompressed and rle compressed true color and grayscale images are supported
+    let rle = match image_type {
+        2 | 3 => false,
+        10 | 11 => true,
+        _ => return Err(Texture2DError::FormatNotSupported),
+    };
+    let (format, bytes_per_color) = match (image_type, bits_per_pixel) {
+        (2, 24) | (10, 24) => (TextureFormat::RGB8, 3),
+        (2, 32) | (10, 32) => (TextureFormat::RGBA8, 4),
+        (3, 8) | (11, 8) => (TextureFormat::R8, 1),
+        _ => return Err(Texture2DError::FormatNotSupported),
+    };
+
+    // read the pixels
+    let color_map_size = if color_map_type == 1 {
+        color_map_len * color_map_depth.div_ceil(8)
+    } else {
+        0
+    };
+    let mut cursor = 18 + id_len + color_map_size;
+    let pixel_count = width * height;
+
+    // check that the file can hold every pixel before allocating, a rle packet of
+    // 1 + bytes_per_color bytes expands to at most 128 pixels
+    let remaining = data.len().saturating_sub(cursor);
+    let max_pixels = if rle {
+        remaining / (1 + bytes_per_color) * 128
+    } else {
+        remaining / bytes_per_color
+    };
+    if pixel_count > max_pixels {
+        return Err(Texture2DError::DecodingError);
+    }
+
+    let mut pixels = Vec::with_capacity(pixel_count * bytes_per_color);
+    while pixels.len() < pixel_count * bytes_per_color {
+        let (count, repeat) = if rle {
+            let packet = match data.get(cursor) {
+                Some(packet) => *packet,
+                None => return Err(Texture2DError::DecodingError),
+            };
+            cursor += 1;
+            ((packet & 0x7f) as usize + 1, packet & 0x80 != 0)
+        } else {
+            (pixel_count, false)
+        };
+
+        let len = if repeat {
+            bytes_per_color
+        } else {
+            count * bytes_per_color
+        };
+        let chunk = match data.get(cursor..cursor + len) {
+            Some(chunk) => chunk,
+            None => return Err(Texture2DError::DecodingError),
+        };
+        cursor += len;
+
+        for i in 0..count {
+            let color = if repeat {
+                chunk
+            } else {
+                &chunk[i * bytes_per_color..(i + 1) * bytes_per_color]
+            };
+            match bytes_per_color {
+                1 => pixels.push(color[0]),
+                3 => pixels.extend_from_slice(&[color[2], color[1], color[0]]),
+                _ => pixels.extend_from_slice(&[color[2], color[1], color[0], color[3]]),
+            }
+        }
+    }
+    pixels.truncate(pixel_count * bytes_per_color);
+
+    // rows are stored bottom to top unless the origin is in the top left
+    if descriptor & 0x20 == 0 {
+        flip_rows(&mut pixels, width * bytes_per_color);
+    }
+
+    Ok(DecodedImage {
+        width: width as _,
+        height: height as _,
+        format,
+        data: pixels,
+    })
+}
+
+#[cfg(feature = "hdr")]
+fn decode_hdr(data: &[u8], options: LoadOptions) -> Result<DecodedImage, Texture2DError> {
+    // the header is a list of lines terminated by an empty line, followed by the resolution
+    let mut cursor = 0;
+    let mut next_line = || -> Result<String, Texture2DError> {
+        let end = match data[cursor..].iter().position(|&b| b == b'\n') {
+            Some(end) => cursor + end,
+            None => return Err(Texture2DError::DecodingError),
+        };
+        let line = String::from_utf8_lossy(&data[cursor..end]).into_owned();
+        cursor = end + 1;
+        Ok(line)
+    };
+    loop {
+        let line = next_line()?;
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" {
+            return Err(Texture2DError::FormatNotSupported);
+        }
+    }
+    let resolution = next_line()?;
+
+    // only the standard "-Y height +X width" orientation is supported
+    let resolution: Vec<&str> = resolution.split_whitespace().collect();
+    let (height, width) = match resolution.as_slice() {
+        ["-Y", height, "+X", width] => match (height.parse::<usize>(), width.parse::<usize>()) {
+            (Ok(height), Ok(width)) => (height, width),
+            _ => return Err(Texture2DError::DecodingError),
+        },
+        _ => return Err(Texture2DError::FormatNotSupported),
+    };
+
+    // check that the file can hold every scanline before allocating, a run length encoded
+    // channel of 127 or fewer pixels takes at least 2 bytes
+    if width == 0 || height == 0 {
+        return Err(Texture2DError::DecodingError);
+    }
+    let min_scanline = (4 + 8 * width.div_ceil(127)).min(width.saturating_mul(4));
+    match min_scanline.checked_mul(height) {
+        Some(min_size) if min_size <= data.len() - cursor => {}
+        _ => return Err(Texture2DError::DecodingError),
+    }
+    let size = width * height * 12;
+
+    // read the rgbe scanlines
+    let mut rgbe = vec![0u8; size / 3];
+    for row in rgbe.chunks_mut(width * 4) {
+        let header = match data.get(cursor..cursor + 4) {
+            Some(header) => header,
+            None => return Err(Texture2DError::DecodingError),
+        };
+
+        // new style run length encoding stores each channel separately
+        if (8..0x8000).contains(&width) && header[0] == 2 && header[1] == 2 && header[2] & 0x80 == 0
+        {
+            if ((header[2] as usize) << 8 | header[3] as usize) != width {
+                return Err(Texture2DError::DecodingError);
+            }
+            cursor += 4;
+            for channel in 0..4 {
+                let mut x = 0;
+                while x < width {
+                    let (count, repeat) = match data.get(cursor) {
+                        Some(&count) if count > 128 => ((count - 128) as usize, true),
+                        Some(&count) => (count as usize, false),
+                        None => return Err(Texture2DError::DecodingError),
+                    };
+                    cursor += 1;
+                    if count == 0 || x + count > width {
+                        return Err(Texture2DError::DecodingError);
+                    }
+                    for i in 0..count {
+                        let value = match data.get(if repeat { cursor } else { cursor + i }) {
+                            Some(&value) => value,
+                            None => return Err(Texture2DError::DecodingError),
+                        };
+                        row[(x + i) * 4 + channel] = value;
+                    }
+                    cursor += if repeat { 1 } else { count };
+                    x += count;
+                }
+            }
+        } else {
+            match data.get(cursor..cursor + width * 4) {
+                Some(flat) => row.copy_from_slice(flat),
+                None => return Err(Texture2DError::DecodingError),
+            }
+            cursor += width * 4;
+        }
+    }
+
+    // convert to floats
+    let format = if options.hdr_half_float {
+        TextureFormat::RGB16F
+    } else {
+        TextureFormat::RGB32F
+    };
+    let mut pixels = Vec::with_capacity(size);
+    for color in rgbe.chunks(4) {
+        let scale = if color[3] == 0 {
+            0.0
+        } else {
+            2f32.powi(color[3] as i32 - 136)
+        };
+        for &channel in &color[..3] {
+            let value = (channel as f32 + 0.5) * scale;
+            if options.hdr_half_float {
+                pixels.extend_from_slice(&f32_to_f16(value).to_ne_bytes());
+            } else {
+                pixels.extend_from_slice(&value.to_ne_bytes());
+            }
+        }
+    }
+
+    Ok(DecodedImage {
+        width: width as _,
+        height: height as _,
+        format,
+        data: pixels,
+    })
+}
+
+/// Decodes a 3D .cube LUT into its size and RGB32F texels, red varying fastest
+///
+/// DOMAIN_MIN and DOMAIN_MAX are validated but not applied, inputs outside [0, 1] have to be
+/// remapped by the shader.
+pub(super) fn decode_cube_lut(data: &[u8]) -> Result<(u32, Vec<u8>), Texture2DError> {
+    let text = match str::from_utf8(data) {
+        Ok(text) => text,
+        Err(_) => return Err(Texture2DError::DecodingError),
+    };
+
+    let mut size = None;
+    let mut texels = Vec::new();
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            // blank lines and comments
+            None => continue,
+            Some(word) if word.starts_with('#') => continue,
+            Some("TITLE") => continue,
+            Some("LUT_3D_SIZE") => match words.next().and_then(|word| word.parse::<u32>().ok()) {
+                Some(lut_size) if (2..=256).contains(&lut_size) => size = Some(lut_size),
+                _ => return Err(Texture2DError::DecodingError),
+            },
+            Some("LUT_1D_SIZE") => return Err(Texture2DError::FormatNotSupported),
+            Some("DOMAIN_MIN") | Some("DOMAIN_MAX") => {
+                if words.filter_map(|word| word.parse::<f32>().ok()).count() != 3 {
+                    return Err(Texture2DError::DecodingError);
+                }
+            }
+            Some(word) => {
+                // every other line is a texel
+                let red = word.parse::<f32>();
+                let green = words.next().map(str::parse::<f32>);
+                let blue = words.next().map(str::parse::<f32>);
+                match (red, green, blue) {
+                    (Ok(red), Some(Ok(green)), Some(Ok(blue))) => {
+                        for value in [red, green, blue].iter() {
+                            texels.extend_from_slice(&value.to_ne_bytes());
+                        }
+                    }
+                    _ => return Err(Texture2DError::DecodingError),
+                }
+            }
+        }
+    }
+
+    // check that every texel is present
+    match size {
+        Some(size) if texels.len() == (size * size * size) as usize * 12 => Ok((size, texels)),
+        Some(_) => Err(Texture2DError::InvalidDataDimensions),
+        None => Err(Texture2DError::DecodingError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "jpeg")]
+    #[test]
+    fn decode_jpeg_converts_cmyk_to_rgb() {
+        // a 16x8 adobe cmyk jpeg, red on the left and 50% grey on the right
+        let image = decode_jpeg(include_bytes!("../tests/fixtures/cmyk.jpg")).unwrap();
+        assert_eq!((image.width, image.height), (16, 8));
+        assert_eq!(image.format, TextureFormat::RGB8);
+        assert_eq!(&image.data[..3], &[255, 0, 0]);
+        assert_eq!(&image.data[8 * 3..8 * 3 + 3], &[127, 127, 127]);
+    }
+}
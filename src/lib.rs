@@ -1,23 +1,63 @@
 pub extern crate gl;
+#[cfg(feature = "jpeg")]
+extern crate jpeg_decoder;
 extern crate png;
 
+pub mod atlas_builder;
+pub mod atlas_manifest;
+pub mod atlas_packer;
 pub mod buffer;
+mod container_formats;
 pub mod draw;
+mod extensions;
+pub mod framebuffer;
 mod gl_buffer_resource;
+mod gl_framebuffer_resource;
 mod gl_program_resource;
+mod gl_renderbuffer_resource;
+mod gl_sampler_resource;
 mod gl_shader_resource;
+mod gl_sync_resource;
 mod gl_texture_resource;
 pub mod glsl_types;
+pub mod gpu_vec;
+mod image_formats;
+mod pixel_store;
 pub mod program;
+pub mod renderbuffer;
+pub mod ring_buffer;
+pub mod sampler;
 pub mod shader;
 pub mod texture;
+pub mod texture_3d;
+pub mod texture_array;
+pub mod texture_atlas;
+pub mod texture_cube;
+pub mod texture_format;
+pub mod texture_multisample;
+pub mod texture_uploader;
 
+pub use self::atlas_builder::*;
+pub use self::atlas_manifest::*;
+pub use self::atlas_packer::*;
 pub use self::buffer::*;
 pub use self::draw::*;
+pub use self::framebuffer::*;
 pub use self::glsl_types::*;
+pub use self::gpu_vec::*;
 pub use self::program::*;
+pub use self::renderbuffer::*;
+pub use self::ring_buffer::*;
+pub use self::sampler::*;
 pub use self::shader::*;
 pub use self::texture::*;
+pub use self::texture_3d::*;
+pub use self::texture_array::*;
+pub use self::texture_atlas::*;
+pub use self::texture_cube::*;
+pub use self::texture_format::*;
+pub use self::texture_multisample::*;
+pub use self::texture_uploader::*;
 
 // the dirtiest of hacks
 pub fn bind_vao() {
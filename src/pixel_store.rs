@@ -0,0 +1,76 @@
+use gl;
+use gl::types::*;
+
+const UNPACK_PARAMETERS: [GLenum; 6] = [
+    gl::UNPACK_ALIGNMENT,
+    gl::UNPACK_ROW_LENGTH,
+    gl::UNPACK_SKIP_PIXELS,
+    gl::UNPACK_SKIP_ROWS,
+    gl::UNPACK_IMAGE_HEIGHT,
+    gl::UNPACK_SKIP_IMAGES,
+];
+
+const PACK_PARAMETERS: [GLenum; 6] = [
+    gl::PACK_ALIGNMENT,
+    gl::PACK_ROW_LENGTH,
+    gl::PACK_SKIP_PIXELS,
+    gl::PACK_SKIP_ROWS,
+    gl::PACK_IMAGE_HEIGHT,
+    gl::PACK_SKIP_IMAGES,
+];
+
+// Sets the pixel store parameters for a transfer, restoring the previous ones when dropped
+pub(super) struct PixelStore {
+    parameters: &'static [GLenum; 6],
+    previous: [GLint; 6],
+}
+
+impl PixelStore {
+    // Byte aligned, tightly packed rows for uploads
+    pub(super) fn unpack() -> Self {
+        Self::unpack_rows(0, 0, 0)
+    }
+
+    // Byte aligned rows `row_length` pixels long for uploads, skipping the first `skip_pixels`
+    // pixels of each row and the first `skip_rows` rows, 0 means tightly packed
+    pub(super) fn unpack_rows(row_length: u32, skip_pixels: u32, skip_rows: u32) -> Self {
+        Self::set(
+            &UNPACK_PARAMETERS,
+            [1, row_length as _, skip_pixels as _, skip_rows as _, 0, 0],
+        )
+    }
+
+    // Byte aligned, tightly packed rows for reads
+    pub(super) fn pack() -> Self {
+        Self::set(&PACK_PARAMETERS, [1, 0, 0, 0, 0, 0])
+    }
+
+    fn set(parameters: &'static [GLenum; 6], values: [GLint; 6]) -> Self {
+        let mut previous = [0; 6];
+        let entries = parameters
+            .iter()
+            .zip(values.iter())
+            .zip(previous.iter_mut());
+        unsafe {
+            for ((&parameter, &value), previous) in entries {
+                gl::GetIntegerv(parameter, previous);
+                gl::PixelStorei(parameter, value);
+            }
+        }
+
+        Self {
+            parameters,
+            previous,
+        }
+    }
+}
+
+impl Drop for PixelStore {
+    fn drop(&mut self) {
+        unsafe {
+            for (&parameter, &value) in self.parameters.iter().zip(self.previous.iter()) {
+                gl::PixelStorei(parameter, value);
+            }
+        }
+    }
+}
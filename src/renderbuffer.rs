@@ -0,0 +1,83 @@
+use gl;
+
+use super::gl_renderbuffer_resource::*;
+use super::texture_format::*;
+
+#[derive(Debug)]
+pub enum RenderbufferError {
+    FormatNotSupported,
+    TooManySamples,
+}
+
+pub struct Renderbuffer {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    pub samples: u32,
+    pub(super) resource: GLRenderbufferResource,
+}
+
+impl Renderbuffer {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            format: TextureFormat::RGBA8,
+            samples: 0,
+            resource: GLRenderbufferResource::new(),
+        }
+    }
+
+    /// Allocates single sampled storage for a Renderbuffer object
+    pub fn init(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Result<(), RenderbufferError> {
+        self.init_multisample(width, height, format, 0)
+    }
+
+    /// Allocates multisampled storage for a Renderbuffer object, a sample count of 0 means single sampled
+    pub fn init_multisample(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        samples: u32,
+    ) -> Result<(), RenderbufferError> {
+        // check for validity
+        if format.is_compressed() {
+            return Err(RenderbufferError::FormatNotSupported);
+        }
+        let mut max_samples = 0;
+        unsafe {
+            gl::GetIntegerv(gl::MAX_SAMPLES, &mut max_samples);
+        }
+        if samples > max_samples as u32 {
+            return Err(RenderbufferError::TooManySamples);
+        }
+
+        // allocate the storage
+        unsafe {
+            gl::BindRenderbuffer(gl::RENDERBUFFER, self.resource.get_raw());
+            gl::RenderbufferStorageMultisample(
+                gl::RENDERBUFFER,
+                samples as _,
+                format.internal_format(),
+                width as _,
+                height as _,
+            );
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+        }
+
+        // Set state
+        self.width = width;
+        self.height = height;
+        self.format = format;
+        self.samples = samples;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+use gl;
+use gl::types::*;
+
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::buffer::*;
+use super::extensions::*;
+use super::gl_buffer_resource::*;
+use super::gl_sync_resource::*;
+
+// gives every RingBuffer a distinct id, so slices can't be drawn from another ring
+static NEXT_RING_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A range of elements pushed to a RingBuffer
+///
+/// Only valid until its frame region is reused, `frames` calls to `next_frame` later. Draws
+/// using a stale slice, or one pushed to another RingBuffer, fail with
+/// `BufferError::OutOfBounds`.
+#[derive(Debug)]
+pub struct RingSlice<T> {
+    phantom: PhantomData<T>,
+    pub(super) offset: usize, // in bytes
+    pub(super) len: usize,
+    pub(super) max_index: Option<usize>, // of element buffers
+    ring: usize,
+    frame: u64,
+}
+
+impl<T> Clone for RingSlice<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for RingSlice<T> {}
+
+impl<T> RingSlice<T> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// A buffer split into one region per frame in flight, for data rewritten every frame
+///
+/// Data is pushed into the region of the current frame. `next_frame` fences the region and
+/// moves on to the next one, waiting until the GL is done with it. With buffer storage, the
+/// buffer stays persistently mapped and pushes are plain copies. Without it, every push maps
+/// the range unsynchronized, which the fences make safe.
+pub struct RingBuffer<T> {
+    phantom: PhantomData<T>,
+    pub(super) buffer_type: GLenum,
+    pub(super) resource: GLBufferResource,
+    id: usize,
+    mapped: *mut u8, // null without buffer storage
+    alignment: usize,
+    region_size: usize,
+    head: usize,
+    frame: u64,
+    fences: Vec<Option<GLSyncResource>>,
+}
+
+impl<T: Copy> RingBuffer<T> {
+    /// Creates a buffer with `frames` regions of `frame_capacity` elements each
+    ///
+    /// Slices of uniform buffers start at the uniform buffer offset alignment, so every push
+    /// may use a little more room than its elements take.
+    pub fn new(
+        buffer_type: GLenum,
+        frames: usize,
+        frame_capacity: usize,
+    ) -> Result<Self, BufferError> {
+        let frames = frames.max(1);
+
+        // slices start at a multiple of the alignment
+        let mut alignment = mem::size_of::<T>().max(1);
+        if buffer_type == gl::UNIFORM_BUFFER {
+            let mut uniform_alignment = 0;
+            unsafe {
+                gl::GetIntegerv(gl::UNIFORM_BUFFER_OFFSET_ALIGNMENT, &mut uniform_alignment);
+            }
+            alignment = alignment.max(uniform_alignment as usize);
+        }
+        let region_size = align(frame_capacity * mem::size_of::<T>(), alignment);
+        let size = region_size * frames;
+
+        // allocate the buffer, mapping it once if possible
+        let resource = GLBufferResource::new();
+        let mut mapped = ptr::null_mut();
+        unsafe {
+            gl::BindBuffer(buffer_type, resource.get_raw());
+            if has_buffer_storage() {
+                let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+                gl::BufferStorage(buffer_type, size as _, ptr::null(), flags);
+                if size > 0 {
+                    mapped = gl::MapBufferRange(buffer_type, 0, size as _, flags) as *mut u8;
+                    if mapped.is_null() {
+                        return Err(BufferError::MapFailed);
+                    }
+                }
+            } else {
+                gl::BufferData(buffer_type, size as _, ptr::null(), gl::STREAM_DRAW);
+            }
+        }
+
+        Ok(Self {
+            phantom: PhantomData,
+            buffer_type,
+            resource,
+            id: NEXT_RING_ID.fetch_add(1, Ordering::Relaxed),
+            mapped,
+            alignment,
+            region_size,
+            head: 0,
+            frame: 0,
+            fences: (0..frames).map(|_| None).collect(),
+        })
+    }
+
+    /// Checks if the buffer is persistently mapped
+    pub fn is_persistent(&self) -> bool {
+        !self.mapped.is_null()
+    }
+
+    /// Copies data into the region of the current frame
+    pub fn push(&mut self, data: &[T]) -> Result<RingSlice<T>, BufferError> {
+        let size = mem::size_of_val(data);
+        if self.head + size > self.region_size {
+            return Err(BufferError::OutOfBounds);
+        }
+
+        let region = (self.frame % self.fences.len() as u64) as usize;
+        let offset = region * self.region_size + self.head;
+        if size > 0 {
+            unsafe {
+                if self.is_persistent() {
+                    let target = self.mapped.add(offset);
+                    ptr::copy_nonoverlapping(data.as_ptr() as *const u8, target, size);
+                } else {
+                    // the fences guarantee the GL isn't reading this region
+                    gl::BindBuffer(self.buffer_type, self.resource.get_raw());
+                    let target = gl::MapBufferRange(
+                        self.buffer_type,
+                        offset as _,
+                        size as _,
+                        gl::MAP_WRITE_BIT
+                            | gl::MAP_INVALIDATE_RANGE_BIT
+                            | gl::MAP_UNSYNCHRONIZED_BIT,
+                    );
+                    if target.is_null() {
+                        return Err(BufferError::MapFailed);
+                    }
+                    ptr::copy_nonoverlapping(data.as_ptr() as *const u8, target as *mut u8, size);
+                    if gl::UnmapBuffer(self.buffer_type) == gl::FALSE {
+                        return Err(BufferError::DataCorrupted);
+                    }
+                }
+            }
+        }
+        self.head = align(self.head + size, self.alignment);
+
+        Ok(RingSlice {
+            phantom: PhantomData,
+            offset,
+            len: data.len(),
+            max_index: max_index(self.buffer_type, data),
+            ring: self.id,
+            frame: self.frame,
+        })
+    }
+
+    /// Fences the current region after the draws issued so far and moves on to the next one,
+    /// blocking until the GL has finished reading it
+    ///
+    /// Returns `BufferError::SyncFailed` if waiting fails, the next region may then still be in
+    /// use by the GL.
+    pub fn next_frame(&mut self) -> Result<(), BufferError> {
+        let region = (self.frame % self.fences.len() as u64) as usize;
+        self.fences[region] = Some(GLSyncResource::new());
+
+        self.frame += 1;
+        self.head = 0;
+        let region = (self.frame % self.fences.len() as u64) as usize;
+        if let Some(fence) = self.fences[region].take() {
+            if fence.wait_forever() == SyncStatus::Failed {
+                return Err(BufferError::SyncFailed);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> RingBuffer<T> {
+    // checks that a slice was pushed to this ring and its region hasn't been reused since
+    pub(super) fn is_current(&self, slice: &RingSlice<T>) -> bool {
+        slice.ring == self.id
+            && slice.frame <= self.frame
+            && self.frame - slice.frame < self.fences.len() as u64
+    }
+}
+
+// rounds `size` up to a multiple of `alignment`
+fn align(size: usize, alignment: usize) -> usize {
+    size.div_ceil(alignment) * alignment
+}
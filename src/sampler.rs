@@ -0,0 +1,154 @@
+use gl;
+use gl::types::*;
+
+use super::extensions::*;
+use super::gl_sampler_resource::*;
+
+/// Describes how a texture is sampled
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerDesc {
+    pub wrap_s: GLenum,
+    pub wrap_t: GLenum,
+    pub wrap_r: GLenum,
+    pub min_filter: GLenum,
+    pub mag_filter: GLenum,
+    /// Values above 1.0 are ignored when anisotropic filtering isn't available
+    pub max_anisotropy: f32,
+    pub lod_bias: f32,
+    pub min_lod: f32,
+    pub max_lod: f32,
+    pub border_color: [f32; 4],
+    /// The comparison function used when sampling depth textures, None disables comparison
+    pub compare_func: Option<GLenum>,
+}
+
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        Self {
+            wrap_s: gl::CLAMP_TO_EDGE,
+            wrap_t: gl::CLAMP_TO_EDGE,
+            wrap_r: gl::CLAMP_TO_EDGE,
+            min_filter: gl::NEAREST,
+            mag_filter: gl::NEAREST,
+            max_anisotropy: 1.0,
+            lod_bias: 0.0,
+            min_lod: -1000.0,
+            max_lod: 1000.0,
+            border_color: [0.0; 4],
+            compare_func: None,
+        }
+    }
+}
+
+impl SamplerDesc {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Depth comparison with bilinear filtering and a far border, for sampler2DShadow uniforms
+    pub fn shadow(compare_func: GLenum) -> Self {
+        Self::default()
+            .with_wrap(gl::CLAMP_TO_BORDER)
+            .with_border_color([1.0; 4])
+            .with_filter(gl::LINEAR, gl::LINEAR)
+            .with_compare_func(compare_func)
+    }
+
+    pub fn with_wrap(mut self, wrap: GLenum) -> Self {
+        self.wrap_s = wrap;
+        self.wrap_t = wrap;
+        self.wrap_r = wrap;
+        self
+    }
+
+    pub fn with_filter(mut self, min_filter: GLenum, mag_filter: GLenum) -> Self {
+        self.min_filter = min_filter;
+        self.mag_filter = mag_filter;
+        self
+    }
+
+    pub fn with_anisotropy(mut self, max_anisotropy: f32) -> Self {
+        self.max_anisotropy = max_anisotropy;
+        self
+    }
+
+    pub fn with_lod(mut self, lod_bias: f32, min_lod: f32, max_lod: f32) -> Self {
+        self.lod_bias = lod_bias;
+        self.min_lod = min_lod;
+        self.max_lod = max_lod;
+        self
+    }
+
+    pub fn with_border_color(mut self, border_color: [f32; 4]) -> Self {
+        self.border_color = border_color;
+        self
+    }
+
+    pub fn with_compare_func(mut self, compare_func: GLenum) -> Self {
+        self.compare_func = Some(compare_func);
+        self
+    }
+
+    // sets every parameter through the given texture or sampler parameter functions
+    pub(super) unsafe fn apply(
+        &self,
+        parameteri: &dyn Fn(GLenum, GLint),
+        parameterf: &dyn Fn(GLenum, GLfloat),
+        parameterfv: &dyn Fn(GLenum, *const GLfloat),
+    ) {
+        parameteri(gl::TEXTURE_WRAP_S, self.wrap_s as _);
+        parameteri(gl::TEXTURE_WRAP_T, self.wrap_t as _);
+        parameteri(gl::TEXTURE_WRAP_R, self.wrap_r as _);
+        parameteri(gl::TEXTURE_MIN_FILTER, self.min_filter as _);
+        parameteri(gl::TEXTURE_MAG_FILTER, self.mag_filter as _);
+        parameterf(gl::TEXTURE_LOD_BIAS, self.lod_bias);
+        parameterf(gl::TEXTURE_MIN_LOD, self.min_lod);
+        parameterf(gl::TEXTURE_MAX_LOD, self.max_lod);
+        parameterfv(gl::TEXTURE_BORDER_COLOR, self.border_color.as_ptr());
+        match self.compare_func {
+            Some(compare_func) => {
+                parameteri(gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE as _);
+                parameteri(gl::TEXTURE_COMPARE_FUNC, compare_func as _);
+            }
+            None => parameteri(gl::TEXTURE_COMPARE_MODE, gl::NONE as _),
+        }
+        if let Some(max) = max_anisotropy() {
+            parameterf(
+                TEXTURE_MAX_ANISOTROPY_EXT,
+                self.max_anisotropy.clamp(1.0, max),
+            );
+        }
+    }
+
+    /// Applies the sampler state to the texture currently bound to `target`
+    pub(super) unsafe fn apply_to_texture(&self, target: GLenum) {
+        self.apply(
+            &|pname, param| gl::TexParameteri(target, pname, param),
+            &|pname, param| gl::TexParameterf(target, pname, param),
+            &|pname, params| gl::TexParameterfv(target, pname, params),
+        );
+    }
+}
+
+/// A GL sampler object, which overrides the sampler state of textures bound to the same unit
+pub struct Sampler {
+    pub desc: SamplerDesc,
+    pub(super) resource: GLSamplerResource,
+}
+
+impl Sampler {
+    pub fn new(desc: SamplerDesc) -> Self {
+        let resource = GLSamplerResource::new();
+
+        unsafe {
+            let handle = resource.get_raw();
+            desc.apply(
+                &|pname, param| gl::SamplerParameteri(handle, pname, param),
+                &|pname, param| gl::SamplerParameterf(handle, pname, param),
+                &|pname, params| gl::SamplerParameterfv(handle, pname, params),
+            );
+        }
+
+        Self { desc, resource }
+    }
+}
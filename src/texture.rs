@@ -2,24 +2,61 @@ use gl;
 use gl::types::*;
 
 use png;
+use png::HasParameters;
 use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::mem;
 use std::path::*;
 
+use super::container_formats::*;
 use super::gl_texture_resource::*;
+use super::image_formats::*;
+use super::pixel_store::*;
+use super::sampler::*;
+use super::texture_format::*;
 
 #[derive(Debug)]
 pub enum Texture2DError {
     FileNotFound,
+    CouldNotWriteFile,
+    DecodingError,
     InvalidDataDimensions,
     OutOfBounds,
     FormatNotSupported,
+    FormatNotSupportedByContext,
+    InvalidMipLevel,
+    TooManySamples,
+    UploadFailed,
+    /// The storage of a texture attached to a Framebuffer can't be replaced
+    AttachedToFramebuffer,
+}
+
+/// Options controlling how image files are decoded and uploaded
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoadOptions {
+    /// Down-converts 16 bit channels to 8 bit instead of uploading them as R16/RGBA16
+    pub strip_16: bool,
+    /// Uploads Radiance HDR images as RGB16F instead of RGB32F
+    pub hdr_half_float: bool,
+    /// Allocates the full mip chain and generates it from the image
+    pub generate_mipmaps: bool,
+    /// Overrides the color space of 8 bit RGB and RGBA images, None uses the color space
+    /// declared by the file, which is sRGB unless a png says otherwise
+    ///
+    /// Use ColorSpace::Linear for data such as normal maps.
+    pub color_space: Option<ColorSpace>,
 }
 
 #[derive(Debug)]
 pub struct Texture2D {
     pub width: u32,
     pub height: u32,
-    pub format: GLenum,
+    pub format: TextureFormat,
+    /// The number of allocated mip levels, 0 if no storage has been allocated
+    pub levels: u32,
+    pub sampler: SamplerDesc,
     pub(super) resource: GLTextureResource,
 }
 
@@ -28,84 +65,210 @@ impl Texture2D {
         Self {
             width: 0,
             height: 0,
-            format: 0,
+            format: TextureFormat::RGBA8,
+            levels: 0,
+            sampler: SamplerDesc::default(),
             resource: GLTextureResource::new(),
         }
     }
 
-    pub fn load_from_file(&mut self, path: &Path) -> Result<(), Texture2DError> {
-        let file = match File::open(path) {
-            Ok(file) => file,
-            Err(_) => return Err(Texture2DError::FileNotFound),
+    /// Allocates immutable storage for a Texture2D object without uploading any data
+    ///
+    /// A `levels` of 0 allocates the full mip chain.
+    ///
+    /// The texture object is kept when the size, format and levels are unchanged. Otherwise it's
+    /// replaced, which fails with AttachedToFramebuffer while a Framebuffer holds the texture.
+    pub fn init_null(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        levels: u32,
+    ) -> Result<(), Texture2DError> {
+        let max_levels = mip_levels(width, height);
+        let levels = match levels {
+            0 => max_levels,
+            levels if levels <= max_levels => levels,
+            _ => return Err(Texture2DError::InvalidMipLevel),
         };
 
-        let png_decoder = png::Decoder::new(file);
-        let (info, mut reader) = match png_decoder.read_info() {
-            Ok((info, reader)) => (info, reader),
-            Err(_) => return Err(Texture2DError::FormatNotSupported),
+        self.allocate(width, height, format, levels)
+    }
+
+    /// Allocates a single level depth texture that compares against the reference value when
+    /// sampled, so it can be bound to a sampler2DShadow uniform
+    pub fn init_shadow_map(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        compare_func: GLenum,
+    ) -> Result<(), Texture2DError> {
+        if !format.is_depth() {
+            return Err(Texture2DError::FormatNotSupported);
+        }
+
+        self.sampler = SamplerDesc::shadow(compare_func);
+        self.allocate(width, height, format, 1)
+    }
+
+    // the current storage of the texture object
+    fn storage(&self) -> TextureStorage {
+        TextureStorage {
+            width: self.width,
+            height: self.height,
+            depth: 1,
+            format: self.format,
+            levels: self.levels,
+        }
+    }
+
+    // allocates immutable storage, replacing the texture object unless the storage is unchanged
+    fn allocate(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        levels: u32,
+    ) -> Result<(), Texture2DError> {
+        let storage = TextureStorage {
+            width,
+            height,
+            depth: 1,
+            format,
+            levels,
         };
+        let current = self.storage();
+        allocate_storage(
+            gl::TEXTURE_2D,
+            &mut self.resource,
+            Some(&self.sampler),
+            current,
+            storage,
+        )?;
 
-        let mut buf = vec![0; info.buffer_size()];
-        reader.next_frame(&mut buf).unwrap();
+        // Set state
+        self.width = width;
+        self.height = height;
+        self.format = format;
+        self.levels = levels;
 
-        let format = match info.color_type {
-            png::ColorType::RGB => gl::RGB,
-            png::ColorType::RGBA => gl::RGBA,
-            _ => return Err(Texture2DError::FormatNotSupported),
+        Ok(())
+    }
+
+    pub fn load_from_file(&mut self, path: &Path) -> Result<(), Texture2DError> {
+        self.load_from_file_with_options(path, LoadOptions::default())
+    }
+
+    /// Loads an image file, detecting its format from the file contents
+    pub fn load_from_file_with_options(
+        &mut self,
+        path: &Path,
+        options: LoadOptions,
+    ) -> Result<(), Texture2DError> {
+        let data = read_file(path)?;
+        let image = match detect_format(path, &data) {
+            Some(ImageFormat::Ktx2) => return self.upload_container(&decode_ktx2(&data)?),
+            Some(ImageFormat::Dds) => return self.upload_container(&decode_dds(&data)?),
+            Some(image_format) => decode(image_format, &data, options)?,
+            None => return Err(Texture2DError::FormatNotSupported),
         };
+        let levels = if options.generate_mipmaps {
+            mip_levels(image.width, image.height)
+        } else {
+            1
+        };
+        self.allocate(image.width, image.height, image.format, levels)?;
+        self.blit_level(0, 0, 0, image.width, image.height, &image.data)?;
+        if options.generate_mipmaps {
+            self.generate_mipmaps()?;
+        }
 
-        self.load_from_pixels(info.width, info.height, format, &buf)
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.resource.get_raw());
+            swizzle_grayscale(gl::TEXTURE_2D, image.format);
+        }
+
+        Ok(())
     }
 
+    /// Allocates a single level Texture2D object and uploads tightly packed data to it
     pub fn load_from_pixels(
         &mut self,
         width: u32,
         height: u32,
-        format: GLenum,
+        format: TextureFormat,
         data: &[u8],
     ) -> Result<(), Texture2DError> {
-        // get the number of bytes per color
-        let bytes_per_color = match format {
-            gl::RGB => 3,
-            gl::RGBA => 4,
-            _ => return Err(Texture2DError::FormatNotSupported),
+        // validate the data before replacing the current storage
+        let bytes_per_color = match format.bytes_per_pixel() {
+            Some(bytes_per_color) => bytes_per_color,
+            None => return Err(Texture2DError::FormatNotSupported),
         };
-
-        // check if the data fits the regions
-        if bytes_per_color * width * height != data.len() as u32 {
+        if packed_size(bytes_per_color, width, height, 1) != Some(data.len()) {
             return Err(Texture2DError::InvalidDataDimensions);
         }
 
-        // upload the data
+        self.allocate(width, height, format, 1)?;
+        self.blit_level(0, 0, 0, width, height, data)
+    }
+
+    // uploads every mip level of a single layer container image
+    fn upload_container(&mut self, image: &ContainerImage) -> Result<(), Texture2DError> {
+        if image.layers != 1 || image.faces != 1 {
+            return Err(Texture2DError::FormatNotSupported);
+        }
+        image.check_support()?;
+
+        self.allocate(
+            image.width,
+            image.height,
+            image.storage.format,
+            image.levels.len() as u32,
+        )?;
         unsafe {
-            gl::BindTexture(gl::TEXTURE_2D, self.resource.get_raw());
-            gl::TexImage2D(
-                gl::TEXTURE_2D,
-                0,
-                format as _,
-                width as i32,
-                height as i32,
-                0,
-                format,
-                gl::UNSIGNED_BYTE,
-                data.as_ptr() as _,
-            );
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as _);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as _);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as _);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as _);
+            let _unpack = PixelStore::unpack();
+            for (level, images) in image.levels.iter().enumerate() {
+                image.upload_image(gl::TEXTURE_2D, level, None, &images[0]);
+            }
         }
 
-        // Set state
-        self.width = width;
-        self.height = height;
-        self.format = format;
+        Ok(())
+    }
+
+    /// Sets the sampler state used when the texture is bound without a Sampler object
+    pub fn set_sampler(&mut self, sampler: SamplerDesc) {
+        self.sampler = sampler;
+        apply_sampler(gl::TEXTURE_2D, &self.resource, &self.sampler);
+    }
+
+    /// Generates the full mip chain from level 0
+    ///
+    /// If fewer levels are allocated, the storage is reallocated and level 0 copied over.
+    pub fn generate_mipmaps(&mut self) -> Result<(), Texture2DError> {
+        let current = self.storage();
+        let storage =
+            generate_mip_chain(gl::TEXTURE_2D, &mut self.resource, &self.sampler, current)?;
+        self.levels = storage.levels;
 
-        //
         Ok(())
     }
 
-    /// Blits a chunk of data to a region of a Texture2D object
+    /// Uploads tightly packed data to a mip level, sized (width >> level, height >> level)
+    pub fn load_level_from_pixels(
+        &mut self,
+        level: u32,
+        data: &[u8],
+    ) -> Result<(), Texture2DError> {
+        if level >= self.levels {
+            return Err(Texture2DError::InvalidMipLevel);
+        }
+        let width = (self.width >> level).max(1);
+        let height = (self.height >> level).max(1);
+        self.blit_level(level, 0, 0, width, height, data)
+    }
+
+    /// Blits a chunk of tightly packed data to a region of a Texture2D object
     pub fn blit(
         &mut self,
         x: u32,
@@ -114,26 +277,77 @@ impl Texture2D {
         height: u32,
         data: Box<[u8]>,
     ) -> Result<(), Texture2DError> {
-        // check if we're blitting out of bounds
-        if x + width > self.width || y + height > self.height {
-            return Err(Texture2DError::OutOfBounds);
-        }
+        self.blit_level(0, x, y, width, height, &data)
+    }
 
-        // get the number of bytes per color
-        let bytes_per_color = match self.format {
-            gl::RGB => 3,
-            gl::RGBA => 4,
-            _ => return Err(Texture2DError::FormatNotSupported),
+    /// Blits a chunk of data to a region of a mip level of a Texture2D object
+    pub fn blit_level(
+        &mut self,
+        level: u32,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> Result<(), Texture2DError> {
+        self.check_region(level, x, y, width, height, data.len())?;
+
+        // blit
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.resource.get_raw());
+            let _unpack = PixelStore::unpack();
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                level as _,
+                x as _,
+                y as _,
+                width as _,
+                height as _,
+                self.format.pixel_format(),
+                self.format.component_type(),
+                data.as_ptr() as _,
+            );
         };
 
-        // check if the data fits the regions
-        if bytes_per_color * width * height != data.len() as u32 {
-            return Err(Texture2DError::InvalidDataDimensions);
+        // return success
+        Ok(())
+    }
+
+    /// Blits a region of a larger image to a Texture2D object
+    ///
+    /// `data` holds rows of `row_length` pixels with no padding between them. `source` is the
+    /// (x, y, width, height) region of the image uploaded to (x, y) of the texture.
+    pub fn blit_sub_image(
+        &mut self,
+        x: u32,
+        y: u32,
+        data: &[u8],
+        row_length: u32,
+        source: (u32, u32, u32, u32),
+    ) -> Result<(), Texture2DError> {
+        let (source_x, source_y, width, height) = source;
+        let bytes_per_color = self.check_bounds(0, x, y, width, height)? as usize;
+
+        // check if the region lies within the image
+        if !fits_within(source_x, width, row_length) {
+            return Err(Texture2DError::OutOfBounds);
+        }
+        if width > 0 && height > 0 {
+            let end = (source_y as usize)
+                .checked_add(height as usize - 1)
+                .and_then(|row| row.checked_mul(row_length as usize))
+                .and_then(|start| start.checked_add((source_x + width) as usize))
+                .and_then(|end| end.checked_mul(bytes_per_color));
+            match end {
+                Some(end) if end <= data.len() => {}
+                _ => return Err(Texture2DError::OutOfBounds),
+            }
         }
 
-        // blit
+        // blit, the gl skips to the region and steps over the rest of each row
         unsafe {
             gl::BindTexture(gl::TEXTURE_2D, self.resource.get_raw());
+            let _unpack = PixelStore::unpack_rows(row_length, source_x, source_y);
             gl::TexSubImage2D(
                 gl::TEXTURE_2D,
                 0,
@@ -141,13 +355,385 @@ impl Texture2D {
                 y as _,
                 width as _,
                 height as _,
-                self.format,
-                gl::UNSIGNED_BYTE,
+                self.format.pixel_format(),
+                self.format.component_type(),
                 data.as_ptr() as _,
             );
-        };
+        }
 
-        // return success
         Ok(())
     }
+
+    // checks that a region lies within a mip level and that `len` bytes of data fill it
+    pub(super) fn check_region(
+        &self,
+        level: u32,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        len: usize,
+    ) -> Result<(), Texture2DError> {
+        let bytes_per_color = self.check_bounds(level, x, y, width, height)?;
+
+        // check if the data fits the regions
+        if packed_size(bytes_per_color, width, height, 1) != Some(len) {
+            return Err(Texture2DError::InvalidDataDimensions);
+        }
+
+        Ok(())
+    }
+
+    // checks that a region lies within a mip level, returning the number of bytes per color
+    fn check_bounds(
+        &self,
+        level: u32,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<u32, Texture2DError> {
+        // check if we're blitting out of bounds
+        if level >= self.levels {
+            return Err(Texture2DError::InvalidMipLevel);
+        }
+        let level_width = (self.width >> level).max(1);
+        let level_height = (self.height >> level).max(1);
+        if !fits_within(x, width, level_width) || !fits_within(y, height, level_height) {
+            return Err(Texture2DError::OutOfBounds);
+        }
+
+        // get the number of bytes per color
+        match self.format.bytes_per_pixel() {
+            Some(bytes_per_color) => Ok(bytes_per_color),
+            None => Err(Texture2DError::FormatNotSupported),
+        }
+    }
+
+    /// Reads the contents of a Texture2D object back into memory, tightly packed
+    pub fn read_pixels(&self, flip: bool) -> Result<Vec<u8>, Texture2DError> {
+        // get the number of bytes per color
+        let bytes_per_color = match self.format.bytes_per_pixel() {
+            Some(bytes_per_color) => bytes_per_color,
+            None => return Err(Texture2DError::FormatNotSupported),
+        };
+
+        // read
+        let row_len = (bytes_per_color * self.width) as usize;
+        let mut data = vec![0u8; row_len * self.height as usize];
+        unsafe {
+            let _pack = PixelStore::pack();
+            gl::BindTexture(gl::TEXTURE_2D, self.resource.get_raw());
+            gl::GetTexImage(
+                gl::TEXTURE_2D,
+                0,
+                self.format.pixel_format(),
+                self.format.component_type(),
+                data.as_mut_ptr() as _,
+            );
+        }
+
+        // gl stores rows bottom to top
+        if flip {
+            flip_rows(&mut data, row_len);
+        }
+
+        Ok(data)
+    }
+
+    /// Saves the contents of a 8 bit RGB or RGBA Texture2D object to a png file
+    ///
+    /// Png files store rows top to bottom. Pass `flip` for textures rendered to by gl, which
+    /// puts row 0 at the bottom, and not for textures loaded from image files, which already
+    /// store the first row of the image at row 0.
+    pub fn save_png(&self, path: &Path, flip: bool) -> Result<(), Texture2DError> {
+        let format = match self.format {
+            TextureFormat::RGB8 | TextureFormat::SRGB8 => gl::RGB,
+            TextureFormat::RGBA8 | TextureFormat::SRGB8Alpha8 => gl::RGBA,
+            _ => return Err(Texture2DError::FormatNotSupported),
+        };
+
+        let data = self.read_pixels(flip)?;
+        match write_png(path, self.width, self.height, format, &data) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(Texture2DError::CouldNotWriteFile),
+        }
+    }
+}
+
+/// A texture object that can be bound to a texture unit by Draw
+pub trait TextureType {
+    /// Binds the texture object to its target on the active texture unit
+    ///
+    /// # Safety
+    ///
+    /// A GL context must be current on the calling thread, the same one the texture was created
+    /// on.
+    unsafe fn bind_texture(&self);
+
+    /// Whether the texture is a cube map, which Draw filters across face edges
+    fn is_cube_map(&self) -> bool {
+        false
+    }
+}
+
+impl TextureType for Texture2D {
+    unsafe fn bind_texture(&self) {
+        gl::BindTexture(gl::TEXTURE_2D, self.resource.get_raw());
+    }
+}
+
+/// Reads the contents of an image file into memory
+pub(super) fn read_file(path: &Path) -> Result<Vec<u8>, Texture2DError> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Err(Texture2DError::FileNotFound),
+    };
+    let mut data = Vec::new();
+    if file.read_to_end(&mut data).is_err() {
+        return Err(Texture2DError::FileNotFound);
+    }
+
+    Ok(data)
+}
+
+/// Reads and decodes an image file, container formats are not supported
+pub(super) fn decode_file(
+    path: &Path,
+    options: LoadOptions,
+) -> Result<DecodedImage, Texture2DError> {
+    let data = read_file(path)?;
+    match detect_format(path, &data) {
+        Some(ImageFormat::Ktx2) | Some(ImageFormat::Dds) | None => {
+            Err(Texture2DError::FormatNotSupported)
+        }
+        Some(image_format) => decode(image_format, &data, options),
+    }
+}
+
+/// Reads and decodes a KTX2 or DDS container file
+pub(super) fn decode_container_file(path: &Path) -> Result<ContainerImage, Texture2DError> {
+    let data = read_file(path)?;
+    let image = match detect_format(path, &data) {
+        Some(ImageFormat::Ktx2) => decode_ktx2(&data)?,
+        Some(ImageFormat::Dds) => decode_dds(&data)?,
+        _ => return Err(Texture2DError::FormatNotSupported),
+    };
+    image.check_support()?;
+
+    Ok(image)
+}
+
+/// Makes the texture bound to `target` sample grayscale formats as gray rather than red
+pub(super) unsafe fn swizzle_grayscale(target: GLenum, format: TextureFormat) {
+    let swizzle = match format {
+        TextureFormat::R8 | TextureFormat::R16 => [gl::RED, gl::RED, gl::RED, gl::ONE],
+        TextureFormat::RG8 | TextureFormat::RG16 => [gl::RED, gl::RED, gl::RED, gl::GREEN],
+        _ => return,
+    };
+    gl::TexParameteriv(
+        target,
+        gl::TEXTURE_SWIZZLE_RGBA,
+        swizzle.as_ptr() as *const GLint,
+    );
+}
+
+/// The size, format and mip levels of the immutable storage of a texture object
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct TextureStorage {
+    pub width: u32,
+    pub height: u32,
+    /// The layers of an array texture or the depth of a volume texture, 1 otherwise
+    pub depth: u32,
+    pub format: TextureFormat,
+    /// The mip levels, or the samples of a multisampled texture
+    pub levels: u32,
+}
+
+/// Allocates immutable storage for a texture object of `target`, replacing the object unless
+/// the storage is unchanged, and returns the previous object if it was replaced
+///
+/// Texture objects attached to a Framebuffer are never replaced, the framebuffer would keep
+/// rendering to the old one.
+pub(super) fn allocate_storage(
+    target: GLenum,
+    resource: &mut GLTextureResource,
+    sampler: Option<&SamplerDesc>,
+    current: TextureStorage,
+    storage: TextureStorage,
+) -> Result<Option<GLTextureResource>, Texture2DError> {
+    // immutable storage can't be resized, so a new texture object is needed
+    let previous = if current == storage {
+        None
+    } else if resource.is_attached() {
+        return Err(Texture2DError::AttachedToFramebuffer);
+    } else {
+        Some(mem::replace(resource, GLTextureResource::new()))
+    };
+
+    unsafe {
+        gl::BindTexture(target, resource.get_raw());
+        if previous.is_some() {
+            let internal_format = storage.format.internal_format();
+            let (width, height) = (storage.width as _, storage.height as _);
+            match target {
+                gl::TEXTURE_2D_MULTISAMPLE => gl::TexStorage2DMultisample(
+                    target,
+                    storage.levels as _,
+                    internal_format,
+                    width,
+                    height,
+                    gl::TRUE,
+                ),
+                gl::TEXTURE_2D | gl::TEXTURE_CUBE_MAP => {
+                    gl::TexStorage2D(target, storage.levels as _, internal_format, width, height)
+                }
+                _ => gl::TexStorage3D(
+                    target,
+                    storage.levels as _,
+                    internal_format,
+                    width,
+                    height,
+                    storage.depth as _,
+                ),
+            }
+            if target != gl::TEXTURE_2D_MULTISAMPLE {
+                gl::TexParameteri(target, gl::TEXTURE_BASE_LEVEL, 0);
+                gl::TexParameteri(target, gl::TEXTURE_MAX_LEVEL, storage.levels as GLint - 1);
+            }
+        }
+        if let Some(sampler) = sampler {
+            sampler.apply_to_texture(target);
+        }
+    }
+
+    Ok(previous)
+}
+
+/// Generates the full mip chain of a 2D, 2D array or cube map texture object from level 0
+///
+/// If fewer levels are allocated, the storage is reallocated and level 0 copied over. Returns
+/// the new storage.
+pub(super) fn generate_mip_chain(
+    target: GLenum,
+    resource: &mut GLTextureResource,
+    sampler: &SamplerDesc,
+    current: TextureStorage,
+) -> Result<TextureStorage, Texture2DError> {
+    // compressed textures can't be rendered to and integer textures can't be filtered
+    if current.levels == 0 || !current.format.is_color() || current.format.is_integer() {
+        return Err(Texture2DError::FormatNotSupported);
+    }
+
+    let storage = TextureStorage {
+        levels: current
+            .levels
+            .max(mip_levels(current.width, current.height)),
+        ..current
+    };
+    let previous = allocate_storage(target, resource, Some(sampler), current, storage)?;
+    unsafe {
+        if let Some(previous) = previous {
+            // cube maps are copied as six layers
+            let depth = match target {
+                gl::TEXTURE_CUBE_MAP => 6,
+                _ => current.depth,
+            };
+            gl::CopyImageSubData(
+                previous.get_raw(),
+                target,
+                0,
+                0,
+                0,
+                0,
+                resource.get_raw(),
+                target,
+                0,
+                0,
+                0,
+                0,
+                current.width as _,
+                current.height as _,
+                depth as _,
+            );
+        }
+        gl::BindTexture(target, resource.get_raw());
+        gl::GenerateMipmap(target);
+    }
+
+    Ok(storage)
+}
+
+/// Sets the sampler state used when a texture object is bound without a Sampler object
+pub(super) fn apply_sampler(target: GLenum, resource: &GLTextureResource, sampler: &SamplerDesc) {
+    unsafe {
+        gl::BindTexture(target, resource.get_raw());
+        sampler.apply_to_texture(target);
+    }
+}
+
+/// Returns the length of a full mip chain for a texture of the given size
+pub(super) fn mip_levels(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+// whether `offset + size` lies within `limit` without overflowing
+pub(super) fn fits_within(offset: u32, size: u32, limit: u32) -> bool {
+    match offset.checked_add(size) {
+        Some(end) => end <= limit,
+        None => false,
+    }
+}
+
+// the size of tightly packed data, None if it doesn't fit in a usize
+pub(super) fn packed_size(
+    bytes_per_color: u32,
+    width: u32,
+    height: u32,
+    depth: u32,
+) -> Option<usize> {
+    (bytes_per_color as usize)
+        .checked_mul(width as usize)?
+        .checked_mul(height as usize)?
+        .checked_mul(depth as usize)
+}
+
+/// Encodes tightly packed 8 bit RGB or RGBA data as a png file
+pub(super) fn write_png(
+    path: &Path,
+    width: u32,
+    height: u32,
+    format: GLenum,
+    data: &[u8],
+) -> io::Result<()> {
+    let color_type = match format {
+        gl::RGB => png::ColorType::RGB,
+        gl::RGBA => png::ColorType::RGBA,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "unsupported format",
+            ))
+        }
+    };
+
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set(color_type).set(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(data)?;
+
+    Ok(())
+}
+
+/// Reverses the order of the rows in a tightly packed image
+pub(super) fn flip_rows(data: &mut [u8], row_len: usize) {
+    if row_len == 0 {
+        return;
+    }
+
+    let rows = data.len() / row_len;
+    for i in 0..rows / 2 {
+        let (top, bottom) = data.split_at_mut((rows - i - 1) * row_len);
+        top[i * row_len..(i + 1) * row_len].swap_with_slice(&mut bottom[..row_len]);
+    }
 }
@@ -0,0 +1,208 @@
+use gl;
+
+use std::path::*;
+
+use super::gl_texture_resource::*;
+use super::image_formats::*;
+use super::pixel_store::*;
+use super::sampler::*;
+use super::texture::*;
+use super::texture_format::*;
+
+/// A volume texture, sampled as sampler3D
+#[derive(Debug)]
+pub struct Texture3D {
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    pub format: TextureFormat,
+    /// The number of allocated mip levels, 0 if no storage has been allocated
+    pub levels: u32,
+    pub sampler: SamplerDesc,
+    pub(super) resource: GLTextureResource,
+}
+
+impl Texture3D {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            depth: 0,
+            format: TextureFormat::RGBA8,
+            levels: 0,
+            sampler: SamplerDesc::default(),
+            resource: GLTextureResource::new(),
+        }
+    }
+
+    /// Allocates immutable storage for a Texture3D object without uploading any data
+    ///
+    /// A `levels` of 0 allocates the full mip chain.
+    ///
+    /// The texture object is kept when the size, format and levels are unchanged. Otherwise it's
+    /// replaced.
+    pub fn init_null(
+        &mut self,
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: TextureFormat,
+        levels: u32,
+    ) -> Result<(), Texture2DError> {
+        let max_levels = mip_levels(width.max(depth), height);
+        let levels = match levels {
+            0 => max_levels,
+            levels if levels <= max_levels => levels,
+            _ => return Err(Texture2DError::InvalidMipLevel),
+        };
+
+        self.allocate(width, height, depth, format, levels)
+    }
+
+    // the current storage of the texture object
+    fn storage(&self) -> TextureStorage {
+        TextureStorage {
+            width: self.width,
+            height: self.height,
+            depth: self.depth,
+            format: self.format,
+            levels: self.levels,
+        }
+    }
+
+    // allocates immutable storage, replacing the texture object unless the storage is unchanged
+    fn allocate(
+        &mut self,
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: TextureFormat,
+        levels: u32,
+    ) -> Result<(), Texture2DError> {
+        let storage = TextureStorage {
+            width,
+            height,
+            depth,
+            format,
+            levels,
+        };
+        let current = self.storage();
+        allocate_storage(
+            gl::TEXTURE_3D,
+            &mut self.resource,
+            Some(&self.sampler),
+            current,
+            storage,
+        )?;
+
+        // Set state
+        self.width = width;
+        self.height = height;
+        self.depth = depth;
+        self.format = format;
+        self.levels = levels;
+
+        Ok(())
+    }
+
+    /// Allocates a single level Texture3D object and uploads tightly packed data to it
+    ///
+    /// The data is stored as `depth` slices of `height` rows.
+    pub fn load_from_pixels(
+        &mut self,
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: TextureFormat,
+        data: &[u8],
+    ) -> Result<(), Texture2DError> {
+        // validate the data before replacing the current storage
+        let bytes_per_color = match format.bytes_per_pixel() {
+            Some(bytes_per_color) => bytes_per_color,
+            None => return Err(Texture2DError::FormatNotSupported),
+        };
+        if packed_size(bytes_per_color, width, height, depth) != Some(data.len()) {
+            return Err(Texture2DError::InvalidDataDimensions);
+        }
+
+        self.allocate(width, height, depth, format, 1)?;
+        self.blit((0, 0, 0), (width, height, depth), data)
+    }
+
+    /// Loads a 3D color grading LUT from a .cube file as an RGB32F texture
+    ///
+    /// Texel coordinates map red to x, green to y and blue to z. LUTs are usually sampled with
+    /// linear filtering, which has to be set with `set_sampler`.
+    pub fn load_lut_from_file(&mut self, path: &Path) -> Result<(), Texture2DError> {
+        let data = read_file(path)?;
+        let (size, texels) = decode_cube_lut(&data)?;
+        self.load_from_pixels(size, size, size, TextureFormat::RGB32F, &texels)
+    }
+
+    /// Sets the sampler state used when the texture is bound without a Sampler object
+    pub fn set_sampler(&mut self, sampler: SamplerDesc) {
+        self.sampler = sampler;
+        apply_sampler(gl::TEXTURE_3D, &self.resource, &self.sampler);
+    }
+
+    /// Blits a chunk of data to a box of a Texture3D object
+    ///
+    /// `offset` and `size` are given as (x, y, z) and (width, height, depth).
+    pub fn blit(
+        &mut self,
+        offset: (u32, u32, u32),
+        size: (u32, u32, u32),
+        data: &[u8],
+    ) -> Result<(), Texture2DError> {
+        let (x, y, z) = offset;
+        let (width, height, depth) = size;
+
+        // check if we're blitting out of bounds
+        if self.levels == 0
+            || !fits_within(x, width, self.width)
+            || !fits_within(y, height, self.height)
+            || !fits_within(z, depth, self.depth)
+        {
+            return Err(Texture2DError::OutOfBounds);
+        }
+
+        // get the number of bytes per color
+        let bytes_per_color = match self.format.bytes_per_pixel() {
+            Some(bytes_per_color) => bytes_per_color,
+            None => return Err(Texture2DError::FormatNotSupported),
+        };
+
+        // check if the data fits the regions
+        if packed_size(bytes_per_color, width, height, depth) != Some(data.len()) {
+            return Err(Texture2DError::InvalidDataDimensions);
+        }
+
+        // blit
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_3D, self.resource.get_raw());
+            let _unpack = PixelStore::unpack();
+            gl::TexSubImage3D(
+                gl::TEXTURE_3D,
+                0,
+                x as _,
+                y as _,
+                z as _,
+                width as _,
+                height as _,
+                depth as _,
+                self.format.pixel_format(),
+                self.format.component_type(),
+                data.as_ptr() as _,
+            );
+        };
+
+        Ok(())
+    }
+}
+
+impl TextureType for Texture3D {
+    unsafe fn bind_texture(&self) {
+        gl::BindTexture(gl::TEXTURE_3D, self.resource.get_raw());
+    }
+}
@@ -0,0 +1,294 @@
+use gl;
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::*;
+
+use super::gl_texture_resource::*;
+use super::pixel_store::*;
+use super::sampler::*;
+use super::texture::*;
+use super::texture_format::*;
+
+/// An array of equally sized 2D textures, sampled as sampler2DArray
+#[derive(Debug)]
+pub struct Texture2DArray {
+    pub width: u32,
+    pub height: u32,
+    pub layers: u32,
+    pub format: TextureFormat,
+    /// The number of allocated mip levels, 0 if no storage has been allocated
+    pub levels: u32,
+    pub sampler: SamplerDesc,
+    pub(super) resource: GLTextureResource,
+}
+
+impl Texture2DArray {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            layers: 0,
+            format: TextureFormat::RGBA8,
+            levels: 0,
+            sampler: SamplerDesc::default(),
+            resource: GLTextureResource::new(),
+        }
+    }
+
+    /// Allocates immutable storage for every layer without uploading any data
+    ///
+    /// A `levels` of 0 allocates the full mip chain.
+    ///
+    /// The texture object is kept when the size, format and levels are unchanged. Otherwise it's
+    /// replaced.
+    pub fn init_null(
+        &mut self,
+        width: u32,
+        height: u32,
+        layers: u32,
+        format: TextureFormat,
+        levels: u32,
+    ) -> Result<(), Texture2DError> {
+        let max_levels = mip_levels(width, height);
+        let levels = match levels {
+            0 => max_levels,
+            levels if levels <= max_levels => levels,
+            _ => return Err(Texture2DError::InvalidMipLevel),
+        };
+
+        self.allocate(width, height, layers, format, levels)
+    }
+
+    // the current storage of the texture object
+    fn storage(&self) -> TextureStorage {
+        TextureStorage {
+            width: self.width,
+            height: self.height,
+            depth: self.layers,
+            format: self.format,
+            levels: self.levels,
+        }
+    }
+
+    // allocates immutable storage, replacing the texture object unless the storage is unchanged
+    fn allocate(
+        &mut self,
+        width: u32,
+        height: u32,
+        layers: u32,
+        format: TextureFormat,
+        levels: u32,
+    ) -> Result<(), Texture2DError> {
+        let storage = TextureStorage {
+            width,
+            height,
+            depth: layers,
+            format,
+            levels,
+        };
+        let current = self.storage();
+        allocate_storage(
+            gl::TEXTURE_2D_ARRAY,
+            &mut self.resource,
+            Some(&self.sampler),
+            current,
+            storage,
+        )?;
+
+        // Set state
+        self.width = width;
+        self.height = height;
+        self.layers = layers;
+        self.format = format;
+        self.levels = levels;
+
+        Ok(())
+    }
+
+    pub fn load_layer_from_file(&mut self, layer: u32, path: &Path) -> Result<(), Texture2DError> {
+        self.load_layer_from_file_with_options(layer, path, LoadOptions::default())
+    }
+
+    /// Loads an image file into a layer, the image must match the size and format of the array
+    pub fn load_layer_from_file_with_options(
+        &mut self,
+        layer: u32,
+        path: &Path,
+        options: LoadOptions,
+    ) -> Result<(), Texture2DError> {
+        let image = decode_file(path, options)?;
+        if image.width != self.width || image.height != self.height {
+            return Err(Texture2DError::InvalidDataDimensions);
+        }
+        if image.format != self.format {
+            return Err(Texture2DError::FormatNotSupported);
+        }
+
+        self.blit_layer(layer, 0, 0, image.width, image.height, &image.data)
+    }
+
+    pub fn load_from_directory(&mut self, path: &Path) -> Result<Vec<PathBuf>, Texture2DError> {
+        self.load_from_directory_with_options(path, LoadOptions::default())
+    }
+
+    /// Loads every png in a directory as a layer, in file name order
+    ///
+    /// The images must share a size and format. Returns the path loaded into each layer.
+    pub fn load_from_directory_with_options(
+        &mut self,
+        path: &Path,
+        options: LoadOptions,
+    ) -> Result<Vec<PathBuf>, Texture2DError> {
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(_) => return Err(Texture2DError::FileNotFound),
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| match path.extension().and_then(OsStr::to_str) {
+                Some(extension) => extension.eq_ignore_ascii_case("png"),
+                None => false,
+            })
+            .collect();
+        paths.sort();
+
+        // the first image decides the size and format of the array
+        let first = match paths.first() {
+            Some(first) => decode_file(first, options)?,
+            None => return Err(Texture2DError::FileNotFound),
+        };
+        let levels = if options.generate_mipmaps {
+            mip_levels(first.width, first.height)
+        } else {
+            1
+        };
+        self.allocate(
+            first.width,
+            first.height,
+            paths.len() as u32,
+            first.format,
+            levels,
+        )?;
+        self.blit_layer(0, 0, 0, first.width, first.height, &first.data)?;
+        for (layer, path) in paths.iter().enumerate().skip(1) {
+            self.load_layer_from_file_with_options(layer as u32, path, options)?;
+        }
+        if options.generate_mipmaps {
+            self.generate_mipmaps()?;
+        }
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.resource.get_raw());
+            swizzle_grayscale(gl::TEXTURE_2D_ARRAY, first.format);
+        }
+
+        Ok(paths)
+    }
+
+    /// Loads a KTX2 or DDS array container with the mip chain of every layer
+    pub fn load_from_file(&mut self, path: &Path) -> Result<(), Texture2DError> {
+        let image = decode_container_file(path)?;
+        if image.faces != 1 {
+            return Err(Texture2DError::FormatNotSupported);
+        }
+
+        self.allocate(
+            image.width,
+            image.height,
+            image.layers,
+            image.storage.format,
+            image.levels.len() as u32,
+        )?;
+        unsafe {
+            let _unpack = PixelStore::unpack();
+            for (level, images) in image.levels.iter().enumerate() {
+                for (layer, data) in images.iter().enumerate() {
+                    image.upload_image(gl::TEXTURE_2D_ARRAY, level, Some(layer as u32), data);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets the sampler state used when the texture is bound without a Sampler object
+    pub fn set_sampler(&mut self, sampler: SamplerDesc) {
+        self.sampler = sampler;
+        apply_sampler(gl::TEXTURE_2D_ARRAY, &self.resource, &self.sampler);
+    }
+
+    /// Generates the full mip chain of every layer from level 0
+    ///
+    /// If fewer levels are allocated, the storage is reallocated and level 0 copied over.
+    pub fn generate_mipmaps(&mut self) -> Result<(), Texture2DError> {
+        let current = self.storage();
+        let storage = generate_mip_chain(
+            gl::TEXTURE_2D_ARRAY,
+            &mut self.resource,
+            &self.sampler,
+            current,
+        )?;
+        self.levels = storage.levels;
+
+        Ok(())
+    }
+
+    /// Blits a chunk of data to a region of a layer
+    pub fn blit_layer(
+        &mut self,
+        layer: u32,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> Result<(), Texture2DError> {
+        // check if we're blitting out of bounds
+        if layer >= self.layers
+            || !fits_within(x, width, self.width)
+            || !fits_within(y, height, self.height)
+        {
+            return Err(Texture2DError::OutOfBounds);
+        }
+
+        // get the number of bytes per color
+        let bytes_per_color = match self.format.bytes_per_pixel() {
+            Some(bytes_per_color) => bytes_per_color,
+            None => return Err(Texture2DError::FormatNotSupported),
+        };
+
+        // check if the data fits the regions
+        if packed_size(bytes_per_color, width, height, 1) != Some(data.len()) {
+            return Err(Texture2DError::InvalidDataDimensions);
+        }
+
+        // blit
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.resource.get_raw());
+            let _unpack = PixelStore::unpack();
+            gl::TexSubImage3D(
+                gl::TEXTURE_2D_ARRAY,
+                0,
+                x as _,
+                y as _,
+                layer as _,
+                width as _,
+                height as _,
+                1,
+                self.format.pixel_format(),
+                self.format.component_type(),
+                data.as_ptr() as _,
+            );
+        };
+
+        Ok(())
+    }
+}
+
+impl TextureType for Texture2DArray {
+    unsafe fn bind_texture(&self) {
+        gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.resource.get_raw());
+    }
+}
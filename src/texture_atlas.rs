@@ -0,0 +1,266 @@
+use gl;
+
+use std::path::*;
+
+use super::atlas_manifest::*;
+use super::atlas_packer::*;
+use super::sampler::*;
+use super::texture::*;
+use super::texture_format::*;
+
+/// A region of a TextureAtlas
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasRegion {
+    /// The pixels of the region, excluding padding
+    pub rect: AtlasRect,
+    /// The normalized texture coordinates of the region as (u0, v0, u1, v1), only valid until
+    /// the atlas grows
+    pub uv: [f32; 4],
+}
+
+/// Packs images into a Texture2D at runtime, growing the texture when it fills up
+pub struct TextureAtlas {
+    pub texture: Texture2D,
+    packer: ShelfPacker,
+    padding: u32,
+    extrude: bool,
+}
+
+impl TextureAtlas {
+    /// Creates an empty atlas, every region is surrounded by `padding` pixels
+    ///
+    /// With `extrude`, the padding repeats the edge pixels of each image instead of being left
+    /// transparent, which avoids bleeding when sampling with linear filtering or mipmaps. The
+    /// width and height must not be 0, as the atlas grows by doubling them.
+    pub fn new(
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        padding: u32,
+        extrude: bool,
+    ) -> Result<Self, Texture2DError> {
+        if format.bytes_per_pixel().is_none() {
+            return Err(Texture2DError::FormatNotSupported);
+        }
+        if width == 0 || height == 0 {
+            return Err(Texture2DError::InvalidDataDimensions);
+        }
+
+        let mut texture = Texture2D::new();
+        texture.init_null(width, height, format, 1)?;
+
+        Ok(Self {
+            texture,
+            packer: ShelfPacker::new(width, height),
+            padding,
+            extrude,
+        })
+    }
+
+    /// Packs tightly packed image data into the atlas, growing it if needed
+    pub fn insert(
+        &mut self,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> Result<AtlasRegion, Texture2DError> {
+        let bytes_per_color = match self.texture.format.bytes_per_pixel() {
+            Some(bytes_per_color) => bytes_per_color,
+            None => return Err(Texture2DError::FormatNotSupported),
+        };
+        if packed_size(bytes_per_color, width, height, 1) != Some(data.len()) {
+            return Err(Texture2DError::InvalidDataDimensions);
+        }
+
+        // find room, doubling the atlas until the image fits
+        let padded_width = width + 2 * self.padding;
+        let padded_height = height + 2 * self.padding;
+        let max_size = max_texture_size();
+        let padded = loop {
+            if let Some(padded) = self.packer.allocate(padded_width, padded_height) {
+                break padded;
+            }
+            let (atlas_width, atlas_height) = (self.packer.width(), self.packer.height());
+            if atlas_width >= max_size && atlas_height >= max_size {
+                return Err(Texture2DError::OutOfBounds);
+            }
+            if atlas_width <= atlas_height || atlas_height >= max_size {
+                self.grow((atlas_width * 2).min(max_size), atlas_height)?;
+            } else {
+                self.grow(atlas_width, (atlas_height * 2).min(max_size))?;
+            }
+        };
+
+        // surround the image with its padding
+        let padded_data = pad_image(
+            data,
+            width as usize,
+            height as usize,
+            bytes_per_color as usize,
+            self.padding as usize,
+            self.extrude,
+        );
+        self.texture.blit_level(
+            0,
+            padded.x,
+            padded.y,
+            padded_width,
+            padded_height,
+            &padded_data,
+        )?;
+
+        let rect = AtlasRect {
+            x: padded.x + self.padding,
+            y: padded.y + self.padding,
+            width,
+            height,
+        };
+        Ok(AtlasRegion {
+            rect,
+            uv: self.uv_rect(rect),
+        })
+    }
+
+    /// Returns a region to the atlas, its pixels are overwritten by later inserts
+    ///
+    /// Returns false if the region isn't currently allocated in the atlas.
+    pub fn free(&mut self, rect: AtlasRect) -> bool {
+        let padded = match (
+            rect.x.checked_sub(self.padding),
+            rect.y.checked_sub(self.padding),
+        ) {
+            (Some(x), Some(y)) => AtlasRect {
+                x,
+                y,
+                width: rect.width + 2 * self.padding,
+                height: rect.height + 2 * self.padding,
+            },
+            _ => return false,
+        };
+        self.packer.free(padded)
+    }
+
+    /// Returns the normalized texture coordinates of a region as (u0, v0, u1, v1)
+    pub fn uv_rect(&self, rect: AtlasRect) -> [f32; 4] {
+        let width = self.texture.width as f32;
+        let height = self.texture.height as f32;
+        [
+            rect.x as f32 / width,
+            rect.y as f32 / height,
+            (rect.x + rect.width) as f32 / width,
+            (rect.y + rect.height) as f32 / height,
+        ]
+    }
+
+    /// Reallocates the atlas texture at a larger size, copying the packed images over
+    ///
+    /// Regions keep their pixel rects, but their normalized texture coordinates change.
+    pub fn grow(&mut self, width: u32, height: u32) -> Result<(), Texture2DError> {
+        let width = width.max(self.texture.width);
+        let height = height.max(self.texture.height);
+
+        let mut texture = Texture2D::new();
+        texture.sampler = self.texture.sampler;
+        texture.init_null(width, height, self.texture.format, 1)?;
+        unsafe {
+            gl::CopyImageSubData(
+                self.texture.resource.get_raw(),
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                0,
+                texture.resource.get_raw(),
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                0,
+                self.texture.width as _,
+                self.texture.height as _,
+                1,
+            );
+        }
+        self.texture = texture;
+        self.packer.grow(width, height);
+
+        Ok(())
+    }
+
+    /// Sets the sampler state of the atlas texture
+    pub fn set_sampler(&mut self, sampler: SamplerDesc) {
+        self.texture.set_sampler(sampler);
+    }
+}
+
+/// An atlas packed ahead of time by `ezgl-atlas`, with its pages loaded as Texture2D objects
+pub struct PackedAtlas {
+    pub pages: Vec<Texture2D>,
+    pub manifest: AtlasManifest,
+}
+
+impl PackedAtlas {
+    /// Loads a JSON or RON manifest and the page images next to it
+    pub fn load(path: &Path) -> Result<Self, Texture2DError> {
+        let manifest = match AtlasManifest::load(path) {
+            Ok(manifest) => manifest,
+            Err(AtlasManifestError::FileNotFound) => return Err(Texture2DError::FileNotFound),
+            Err(_) => return Err(Texture2DError::DecodingError),
+        };
+
+        // page paths are relative to the manifest
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut pages = Vec::with_capacity(manifest.pages.len());
+        for page in manifest.pages.iter() {
+            let mut texture = Texture2D::new();
+            texture.load_from_file(&directory.join(page))?;
+            pages.push(texture);
+        }
+        if manifest
+            .sprites
+            .values()
+            .any(|sprite| sprite.page as usize >= pages.len())
+        {
+            return Err(Texture2DError::DecodingError);
+        }
+
+        Ok(Self { pages, manifest })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AtlasSprite> {
+        self.manifest.sprites.get(name)
+    }
+
+    /// Returns the page holding an image and its normalized texture coordinates as
+    /// (u0, v0, u1, v1)
+    pub fn uv_rect(&self, name: &str) -> Option<(&Texture2D, [f32; 4])> {
+        let sprite = self.get(name)?;
+        let page = &self.pages[sprite.page as usize];
+        let width = page.width as f32;
+        let height = page.height as f32;
+        Some((
+            page,
+            [
+                sprite.rect.x as f32 / width,
+                sprite.rect.y as f32 / height,
+                (sprite.rect.x + sprite.rect.width) as f32 / width,
+                (sprite.rect.y + sprite.rect.height) as f32 / height,
+            ],
+        ))
+    }
+
+    pub fn set_sampler(&mut self, sampler: SamplerDesc) {
+        for page in self.pages.iter_mut() {
+            page.set_sampler(sampler);
+        }
+    }
+}
+
+// returns the largest width or height of a texture supported by the context
+fn max_texture_size() -> u32 {
+    let mut max_size = 0;
+    unsafe {
+        gl::GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut max_size);
+    }
+    max_size as u32
+}
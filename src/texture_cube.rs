@@ -0,0 +1,396 @@
+use gl;
+use gl::types::*;
+
+use std::f32::consts::PI;
+use std::path::*;
+
+use super::gl_texture_resource::*;
+use super::image_formats::*;
+use super::pixel_store::*;
+use super::sampler::*;
+use super::texture::*;
+use super::texture_format::*;
+
+/// A cube map texture, sampled as samplerCube
+///
+/// Faces are indexed in GL order: +X, -X, +Y, -Y, +Z, -Z. Draw enables seamless filtering
+/// across face edges while a cube map is bound.
+#[derive(Debug)]
+pub struct TextureCube {
+    /// The width and height of every face
+    pub size: u32,
+    pub format: TextureFormat,
+    /// The number of allocated mip levels, 0 if no storage has been allocated
+    pub levels: u32,
+    pub sampler: SamplerDesc,
+    pub(super) resource: GLTextureResource,
+}
+
+impl TextureCube {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            size: 0,
+            format: TextureFormat::RGBA8,
+            levels: 0,
+            sampler: SamplerDesc::default(),
+            resource: GLTextureResource::new(),
+        }
+    }
+
+    /// Allocates immutable storage for every face without uploading any data
+    ///
+    /// A `levels` of 0 allocates the full mip chain.
+    ///
+    /// The texture object is kept when the size, format and levels are unchanged. Otherwise it's
+    /// replaced.
+    pub fn init_null(
+        &mut self,
+        size: u32,
+        format: TextureFormat,
+        levels: u32,
+    ) -> Result<(), Texture2DError> {
+        let max_levels = mip_levels(size, size);
+        let levels = match levels {
+            0 => max_levels,
+            levels if levels <= max_levels => levels,
+            _ => return Err(Texture2DError::InvalidMipLevel),
+        };
+
+        self.allocate(size, format, levels)
+    }
+
+    // the current storage of the texture object
+    fn storage(&self) -> TextureStorage {
+        TextureStorage {
+            width: self.size,
+            height: self.size,
+            depth: 1,
+            format: self.format,
+            levels: self.levels,
+        }
+    }
+
+    // allocates immutable storage, replacing the texture object unless the storage is unchanged
+    fn allocate(
+        &mut self,
+        size: u32,
+        format: TextureFormat,
+        levels: u32,
+    ) -> Result<(), Texture2DError> {
+        let storage = TextureStorage {
+            width: size,
+            height: size,
+            depth: 1,
+            format,
+            levels,
+        };
+        let current = self.storage();
+        allocate_storage(
+            gl::TEXTURE_CUBE_MAP,
+            &mut self.resource,
+            Some(&self.sampler),
+            current,
+            storage,
+        )?;
+
+        // Set state
+        self.size = size;
+        self.format = format;
+        self.levels = levels;
+
+        Ok(())
+    }
+
+    pub fn load_from_files(&mut self, faces: [&Path; 6]) -> Result<(), Texture2DError> {
+        self.load_from_files_with_options(faces, LoadOptions::default())
+    }
+
+    /// Loads six square images of the same size and format as the faces of the cube map
+    pub fn load_from_files_with_options(
+        &mut self,
+        faces: [&Path; 6],
+        options: LoadOptions,
+    ) -> Result<(), Texture2DError> {
+        // decode every face before replacing the current storage
+        let mut images: Vec<DecodedImage> = Vec::with_capacity(6);
+        for path in faces.iter() {
+            let image = decode_file(path, options)?;
+            if image.width != image.height {
+                return Err(Texture2DError::InvalidDataDimensions);
+            }
+            if let Some(first) = images.first() {
+                if image.width != first.width || image.format != first.format {
+                    return Err(Texture2DError::InvalidDataDimensions);
+                }
+            }
+            images.push(image);
+        }
+
+        let size = images[0].width;
+        let format = images[0].format;
+        self.upload_faces(size, format, &images, options)
+    }
+
+    /// Loads a KTX2 or DDS cube map container with the mip chain of every face
+    pub fn load_from_file(&mut self, path: &Path) -> Result<(), Texture2DError> {
+        let image = decode_container_file(path)?;
+        if image.faces != 6 || image.layers != 1 || image.width != image.height {
+            return Err(Texture2DError::FormatNotSupported);
+        }
+
+        self.allocate(image.width, image.storage.format, image.levels.len() as u32)?;
+        unsafe {
+            let _unpack = PixelStore::unpack();
+            for (level, images) in image.levels.iter().enumerate() {
+                for (face, data) in images.iter().enumerate() {
+                    let target = gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as GLenum;
+                    image.upload_image(target, level, None, data);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn load_from_equirectangular(
+        &mut self,
+        path: &Path,
+        size: u32,
+    ) -> Result<(), Texture2DError> {
+        self.load_from_equirectangular_with_options(path, size, LoadOptions::default())
+    }
+
+    /// Loads an equirectangular (latitude-longitude) image, projecting it onto faces of `size`
+    ///
+    /// The projection is done on the CPU with bilinear filtering, usually from a Radiance HDR
+    /// environment map. 8 bit and 32 bit float images are supported.
+    pub fn load_from_equirectangular_with_options(
+        &mut self,
+        path: &Path,
+        size: u32,
+        options: LoadOptions,
+    ) -> Result<(), Texture2DError> {
+        // project at full precision, then convert to half floats if asked to
+        let full_options = LoadOptions {
+            hdr_half_float: false,
+            ..options
+        };
+        let image = decode_file(path, full_options)?;
+        let mut faces = project_equirectangular(&image, size)?;
+        let mut format = image.format;
+        if options.hdr_half_float && format == TextureFormat::RGB32F {
+            format = TextureFormat::RGB16F;
+            for face in faces.iter_mut() {
+                let mut data = Vec::with_capacity(face.data.len() / 2);
+                for value in face.data.chunks(4) {
+                    let value = f32::from_ne_bytes([value[0], value[1], value[2], value[3]]);
+                    data.extend_from_slice(&f32_to_f16(value).to_ne_bytes());
+                }
+                face.data = data;
+                face.format = format;
+            }
+        }
+
+        self.upload_faces(size, format, &faces, options)
+    }
+
+    // allocates storage for six decoded faces and uploads them
+    fn upload_faces(
+        &mut self,
+        size: u32,
+        format: TextureFormat,
+        faces: &[DecodedImage],
+        options: LoadOptions,
+    ) -> Result<(), Texture2DError> {
+        let levels = if options.generate_mipmaps {
+            mip_levels(size, size)
+        } else {
+            1
+        };
+        self.allocate(size, format, levels)?;
+        for (face, image) in faces.iter().enumerate() {
+            self.blit_face(face as u32, 0, 0, size, size, &image.data)?;
+        }
+        if options.generate_mipmaps {
+            self.generate_mipmaps()?;
+        }
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.resource.get_raw());
+            swizzle_grayscale(gl::TEXTURE_CUBE_MAP, format);
+        }
+
+        Ok(())
+    }
+
+    /// Sets the sampler state used when the texture is bound without a Sampler object
+    pub fn set_sampler(&mut self, sampler: SamplerDesc) {
+        self.sampler = sampler;
+        apply_sampler(gl::TEXTURE_CUBE_MAP, &self.resource, &self.sampler);
+    }
+
+    /// Generates the full mip chain of every face from level 0
+    ///
+    /// If fewer levels are allocated, the storage is reallocated and level 0 copied over.
+    pub fn generate_mipmaps(&mut self) -> Result<(), Texture2DError> {
+        let current = self.storage();
+        let storage = generate_mip_chain(
+            gl::TEXTURE_CUBE_MAP,
+            &mut self.resource,
+            &self.sampler,
+            current,
+        )?;
+        self.levels = storage.levels;
+
+        Ok(())
+    }
+
+    /// Blits a chunk of data to a region of a face
+    pub fn blit_face(
+        &mut self,
+        face: u32,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> Result<(), Texture2DError> {
+        // check if we're blitting out of bounds
+        if self.levels == 0
+            || face >= 6
+            || !fits_within(x, width, self.size)
+            || !fits_within(y, height, self.size)
+        {
+            return Err(Texture2DError::OutOfBounds);
+        }
+
+        // get the number of bytes per color
+        let bytes_per_color = match self.format.bytes_per_pixel() {
+            Some(bytes_per_color) => bytes_per_color,
+            None => return Err(Texture2DError::FormatNotSupported),
+        };
+
+        // check if the data fits the regions
+        if packed_size(bytes_per_color, width, height, 1) != Some(data.len()) {
+            return Err(Texture2DError::InvalidDataDimensions);
+        }
+
+        // blit
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.resource.get_raw());
+            let _unpack = PixelStore::unpack();
+            gl::TexSubImage2D(
+                gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                0,
+                x as _,
+                y as _,
+                width as _,
+                height as _,
+                self.format.pixel_format(),
+                self.format.component_type(),
+                data.as_ptr() as _,
+            );
+        };
+
+        Ok(())
+    }
+}
+
+impl TextureType for TextureCube {
+    unsafe fn bind_texture(&self) {
+        gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.resource.get_raw());
+    }
+
+    fn is_cube_map(&self) -> bool {
+        true
+    }
+}
+
+// resamples an equirectangular image into six cube map faces
+fn project_equirectangular(
+    image: &DecodedImage,
+    size: u32,
+) -> Result<Vec<DecodedImage>, Texture2DError> {
+    let component_size = match image.format.component_type() {
+        gl::UNSIGNED_BYTE => 1,
+        gl::FLOAT => 4,
+        _ => return Err(Texture2DError::FormatNotSupported),
+    };
+    let channels = match image.format.bytes_per_pixel() {
+        Some(bytes_per_pixel) if image.format.is_color() => {
+            (bytes_per_pixel / component_size) as usize
+        }
+        _ => return Err(Texture2DError::FormatNotSupported),
+    };
+    let (width, height) = (image.width as usize, image.height as usize);
+    if width == 0 || height == 0 {
+        return Err(Texture2DError::InvalidDataDimensions);
+    }
+
+    // reads a channel of a pixel, wrapping horizontally and clamping vertically
+    let read = |x: isize, y: isize, channel: usize| -> f32 {
+        let x = x.rem_euclid(width as isize) as usize;
+        let y = y.max(0).min(height as isize - 1) as usize;
+        let index = ((y * width + x) * channels + channel) * component_size as usize;
+        match component_size {
+            1 => image.data[index] as f32,
+            _ => f32::from_ne_bytes([
+                image.data[index],
+                image.data[index + 1],
+                image.data[index + 2],
+                image.data[index + 3],
+            ]),
+        }
+    };
+
+    let mut faces = Vec::with_capacity(6);
+    for face in 0..6 {
+        let mut data =
+            Vec::with_capacity((size * size) as usize * channels * component_size as usize);
+        for y in 0..size {
+            for x in 0..size {
+                // face coordinates in [-1, 1], with the first row at the top of the face
+                let s = 2.0 * (x as f32 + 0.5) / size as f32 - 1.0;
+                let t = 2.0 * (y as f32 + 0.5) / size as f32 - 1.0;
+                let (dx, dy, dz) = match face {
+                    0 => (1.0, -t, -s),
+                    1 => (-1.0, -t, s),
+                    2 => (s, 1.0, t),
+                    3 => (s, -1.0, -t),
+                    4 => (s, -t, 1.0),
+                    _ => (-s, -t, -1.0),
+                };
+                let len: f32 = (dx * dx + dy * dy + dz * dz).sqrt();
+
+                // direction to texel coordinates of the equirectangular image
+                let u = 0.5 + dz.atan2(dx) / (2.0 * PI);
+                let v = 0.5 - (dy / len).asin() / PI;
+                let fx = u * width as f32 - 0.5;
+                let fy = v * height as f32 - 0.5;
+                let (x0, y0) = (fx.floor(), fy.floor());
+                let (wx, wy) = (fx - x0, fy - y0);
+                let (x0, y0) = (x0 as isize, y0 as isize);
+
+                for channel in 0..channels {
+                    let top = read(x0, y0, channel) * (1.0 - wx) + read(x0 + 1, y0, channel) * wx;
+                    let bottom =
+                        read(x0, y0 + 1, channel) * (1.0 - wx) + read(x0 + 1, y0 + 1, channel) * wx;
+                    let value = top * (1.0 - wy) + bottom * wy;
+                    match component_size {
+                        1 => data.push(value.round().clamp(0.0, 255.0) as u8),
+                        _ => data.extend_from_slice(&value.to_ne_bytes()),
+                    }
+                }
+            }
+        }
+        faces.push(DecodedImage {
+            width: size,
+            height: size,
+            format: image.format,
+            data,
+        });
+    }
+
+    Ok(faces)
+}
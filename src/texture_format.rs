@@ -0,0 +1,223 @@
+use gl;
+use gl::types::*;
+
+/// The color space that the components of a color format are encoded in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+}
+
+/// A sized internal format, along with the pixel format and component type used to transfer it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    R8,
+    RG8,
+    RGB8,
+    RGBA8,
+    SRGB8,
+    SRGB8Alpha8,
+    R16,
+    RG16,
+    RGB16,
+    RGBA16,
+    R16F,
+    RG16F,
+    RGB16F,
+    RGBA16F,
+    R32F,
+    RG32F,
+    RGB32F,
+    RGBA32F,
+    R11FG11FB10F,
+    RGB10A2,
+    R8UI,
+    RG8UI,
+    RGBA8UI,
+    R16UI,
+    R32UI,
+    R32I,
+    Depth16,
+    Depth24,
+    Depth32F,
+    Depth24Stencil8,
+    Depth32FStencil8,
+    Stencil8,
+    /// A block compressed internal format, ie. gl::COMPRESSED_RGBA_BPTC_UNORM
+    Compressed(GLenum),
+}
+
+impl TextureFormat {
+    /// The sized internal format used to allocate storage
+    pub fn internal_format(&self) -> GLenum {
+        use self::TextureFormat::*;
+        match *self {
+            R8 => gl::R8,
+            RG8 => gl::RG8,
+            RGB8 => gl::RGB8,
+            RGBA8 => gl::RGBA8,
+            SRGB8 => gl::SRGB8,
+            SRGB8Alpha8 => gl::SRGB8_ALPHA8,
+            R16 => gl::R16,
+            RG16 => gl::RG16,
+            RGB16 => gl::RGB16,
+            RGBA16 => gl::RGBA16,
+            R16F => gl::R16F,
+            RG16F => gl::RG16F,
+            RGB16F => gl::RGB16F,
+            RGBA16F => gl::RGBA16F,
+            R32F => gl::R32F,
+            RG32F => gl::RG32F,
+            RGB32F => gl::RGB32F,
+            RGBA32F => gl::RGBA32F,
+            R11FG11FB10F => gl::R11F_G11F_B10F,
+            RGB10A2 => gl::RGB10_A2,
+            R8UI => gl::R8UI,
+            RG8UI => gl::RG8UI,
+            RGBA8UI => gl::RGBA8UI,
+            R16UI => gl::R16UI,
+            R32UI => gl::R32UI,
+            R32I => gl::R32I,
+            Depth16 => gl::DEPTH_COMPONENT16,
+            Depth24 => gl::DEPTH_COMPONENT24,
+            Depth32F => gl::DEPTH_COMPONENT32F,
+            Depth24Stencil8 => gl::DEPTH24_STENCIL8,
+            Depth32FStencil8 => gl::DEPTH32F_STENCIL8,
+            Stencil8 => gl::STENCIL_INDEX8,
+            Compressed(internal_format) => internal_format,
+        }
+    }
+
+    /// The pixel format of client side data, compressed formats return their internal format
+    pub fn pixel_format(&self) -> GLenum {
+        use self::TextureFormat::*;
+        match *self {
+            R8 | R16 | R16F | R32F => gl::RED,
+            RG8 | RG16 | RG16F | RG32F => gl::RG,
+            RGB8 | SRGB8 | RGB16 | RGB16F | RGB32F | R11FG11FB10F => gl::RGB,
+            RGBA8 | SRGB8Alpha8 | RGBA16 | RGBA16F | RGBA32F | RGB10A2 => gl::RGBA,
+            R8UI | R16UI | R32UI | R32I => gl::RED_INTEGER,
+            RG8UI => gl::RG_INTEGER,
+            RGBA8UI => gl::RGBA_INTEGER,
+            Depth16 | Depth24 | Depth32F => gl::DEPTH_COMPONENT,
+            Depth24Stencil8 | Depth32FStencil8 => gl::DEPTH_STENCIL,
+            Stencil8 => gl::STENCIL_INDEX,
+            Compressed(internal_format) => internal_format,
+        }
+    }
+
+    /// The component type of client side data, compressed formats return 0
+    pub fn component_type(&self) -> GLenum {
+        use self::TextureFormat::*;
+        match *self {
+            R8 | RG8 | RGB8 | RGBA8 | SRGB8 | SRGB8Alpha8 | R8UI | RG8UI | RGBA8UI | Stencil8 => {
+                gl::UNSIGNED_BYTE
+            }
+            R16 | RG16 | RGB16 | RGBA16 | R16UI | Depth16 => gl::UNSIGNED_SHORT,
+            R16F | RG16F | RGB16F | RGBA16F => gl::HALF_FLOAT,
+            R32F | RG32F | RGB32F | RGBA32F | Depth32F => gl::FLOAT,
+            R11FG11FB10F => gl::UNSIGNED_INT_10F_11F_11F_REV,
+            RGB10A2 => gl::UNSIGNED_INT_2_10_10_10_REV,
+            R32UI | Depth24 => gl::UNSIGNED_INT,
+            R32I => gl::INT,
+            Depth24Stencil8 => gl::UNSIGNED_INT_24_8,
+            Depth32FStencil8 => gl::FLOAT_32_UNSIGNED_INT_24_8_REV,
+            Compressed(_) => 0,
+        }
+    }
+
+    /// The size of a tightly packed pixel of client side data, None for compressed formats
+    pub fn bytes_per_pixel(&self) -> Option<u32> {
+        use self::TextureFormat::*;
+        let bytes = match *self {
+            R8 | R8UI | Stencil8 => 1,
+            RG8 | RG8UI | R16 | R16UI | R16F | Depth16 => 2,
+            RGB8 | SRGB8 => 3,
+            RGBA8 | SRGB8Alpha8 | RGBA8UI | RG16 | RG16F | R32F | R32UI | R32I => 4,
+            R11FG11FB10F | RGB10A2 | Depth24 | Depth32F | Depth24Stencil8 => 4,
+            RGB16 | RGB16F => 6,
+            RGBA16 | RGBA16F | RG32F | Depth32FStencil8 => 8,
+            RGB32F => 12,
+            RGBA32F => 16,
+            Compressed(_) => return None,
+        };
+        Some(bytes)
+    }
+
+    /// Returns the variant of an 8 bit RGB or RGBA format in the given color space
+    ///
+    /// Formats without an sRGB variant are returned unchanged.
+    pub fn with_color_space(self, color_space: ColorSpace) -> Self {
+        use self::TextureFormat::*;
+        match (self, color_space) {
+            (RGB8, ColorSpace::Srgb) => SRGB8,
+            (RGBA8, ColorSpace::Srgb) => SRGB8Alpha8,
+            (SRGB8, ColorSpace::Linear) => RGB8,
+            (SRGB8Alpha8, ColorSpace::Linear) => RGBA8,
+            (format, _) => format,
+        }
+    }
+
+    pub fn is_srgb(&self) -> bool {
+        matches!(*self, TextureFormat::SRGB8 | TextureFormat::SRGB8Alpha8)
+    }
+
+    pub fn is_color(&self) -> bool {
+        !self.is_depth() && !self.is_stencil() && !self.is_compressed()
+    }
+
+    pub fn is_depth(&self) -> bool {
+        use self::TextureFormat::*;
+        matches!(
+            *self,
+            Depth16 | Depth24 | Depth32F | Depth24Stencil8 | Depth32FStencil8
+        )
+    }
+
+    pub fn is_stencil(&self) -> bool {
+        use self::TextureFormat::*;
+        matches!(*self, Depth24Stencil8 | Depth32FStencil8 | Stencil8)
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        matches!(*self, TextureFormat::Compressed(_))
+    }
+
+    /// Checks if the format is sampled as unnormalized integers, which can't be filtered
+    pub fn is_integer(&self) -> bool {
+        use self::TextureFormat::*;
+        matches!(*self, R8UI | RG8UI | RGBA8UI | R16UI | R32UI | R32I)
+    }
+
+    /// The framebuffer attachment point for depth and stencil formats
+    pub(super) fn depth_stencil_attachment(&self) -> Option<GLenum> {
+        match (self.is_depth(), self.is_stencil()) {
+            (true, true) => Some(gl::DEPTH_STENCIL_ATTACHMENT),
+            (true, false) => Some(gl::DEPTH_ATTACHMENT),
+            (false, true) => Some(gl::STENCIL_ATTACHMENT),
+            (false, false) => None,
+        }
+    }
+}
+
+/// Converts a float to the bits of a half float, truncating the mantissa
+pub(super) fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent >= 0x1f {
+        // too large, round to infinity
+        sign | 0x7c00
+    } else if exponent <= 0 {
+        // too small for a normal half float, round to a subnormal or zero
+        if exponent < -10 {
+            sign
+        } else {
+            sign | ((mantissa | 0x80_0000) >> (14 - exponent)) as u16
+        }
+    } else {
+        sign | (exponent as u16) << 10 | (mantissa >> 13) as u16
+    }
+}
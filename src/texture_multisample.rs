@@ -0,0 +1,96 @@
+use gl;
+
+use super::gl_texture_resource::*;
+use super::texture::*;
+use super::texture_format::*;
+
+/// A multisampled 2D texture, used as a framebuffer attachment and sampled as sampler2DMS
+#[derive(Debug)]
+pub struct Texture2DMultisample {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    pub samples: u32,
+    pub(super) resource: GLTextureResource,
+}
+
+impl Texture2DMultisample {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            format: TextureFormat::RGBA8,
+            samples: 0,
+            resource: GLTextureResource::new(),
+        }
+    }
+
+    /// Allocates immutable multisampled storage, with the same sample locations for every texel
+    ///
+    /// The texture object is kept when the size, format and samples are unchanged. Otherwise it's
+    /// replaced, which fails with AttachedToFramebuffer while a Framebuffer holds the texture.
+    pub fn init(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        samples: u32,
+    ) -> Result<(), Texture2DError> {
+        // check for validity
+        if format.is_compressed() {
+            return Err(Texture2DError::FormatNotSupported);
+        }
+        let mut max_samples = 0;
+        unsafe {
+            let limit = if format.is_color() {
+                gl::MAX_COLOR_TEXTURE_SAMPLES
+            } else {
+                gl::MAX_DEPTH_TEXTURE_SAMPLES
+            };
+            gl::GetIntegerv(limit, &mut max_samples);
+        }
+        if samples > max_samples as u32 {
+            return Err(Texture2DError::TooManySamples);
+        }
+
+        // a multisampled texture has at least one sample
+        let samples = samples.max(1);
+
+        let current = TextureStorage {
+            width: self.width,
+            height: self.height,
+            depth: 1,
+            format: self.format,
+            levels: self.samples,
+        };
+        let storage = TextureStorage {
+            width,
+            height,
+            depth: 1,
+            format,
+            levels: samples,
+        };
+        allocate_storage(
+            gl::TEXTURE_2D_MULTISAMPLE,
+            &mut self.resource,
+            None,
+            current,
+            storage,
+        )?;
+
+        // Set state
+        self.width = width;
+        self.height = height;
+        self.format = format;
+        self.samples = samples;
+
+        Ok(())
+    }
+}
+
+impl TextureType for Texture2DMultisample {
+    unsafe fn bind_texture(&self) {
+        gl::BindTexture(gl::TEXTURE_2D_MULTISAMPLE, self.resource.get_raw());
+    }
+}
@@ -0,0 +1,164 @@
+use gl;
+
+use std::ptr;
+
+use super::buffer::*;
+use super::gl_sync_resource::*;
+use super::pixel_store::*;
+use super::texture::*;
+
+/// Identifies an upload issued by a TextureUploader
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UploadId(u64);
+
+// a pixel unpack buffer and the upload that last used it
+struct UploadSlot {
+    buffer: Buffer<u8>,
+    pending: Option<(UploadId, GLSyncResource)>,
+}
+
+/// Streams texture data through a ring of pixel unpack buffers
+///
+/// `enqueue` copies the data into a buffer and returns immediately, the GL copies it into the
+/// texture asynchronously. Data can be decoded on any thread, only `enqueue` needs the context.
+/// When every buffer is still in use, `enqueue` waits for the oldest upload to finish.
+pub struct TextureUploader {
+    slots: Vec<UploadSlot>,
+    next_slot: usize,
+    next_id: u64,
+}
+
+impl TextureUploader {
+    /// Creates an uploader with `buffers` buffers of `buffer_size` bytes each
+    ///
+    /// Buffers grow to fit uploads larger than `buffer_size`.
+    pub fn new(buffers: usize, buffer_size: usize) -> Result<Self, Texture2DError> {
+        let slots = (0..buffers.max(1))
+            .map(|_| {
+                let mut buffer = Buffer::new();
+                buffer.init_null(
+                    gl::PIXEL_UNPACK_BUFFER,
+                    buffer_size,
+                    BufferUsage::StreamDraw,
+                )?;
+                Ok(UploadSlot {
+                    buffer,
+                    pending: None,
+                })
+            })
+            .collect::<Result<Vec<_>, BufferError>>();
+        unsafe {
+            gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, 0);
+        }
+
+        match slots {
+            Ok(slots) => Ok(Self {
+                slots,
+                next_slot: 0,
+                next_id: 0,
+            }),
+            Err(_) => Err(Texture2DError::UploadFailed),
+        }
+    }
+
+    /// Uploads tightly packed data to a region of a Texture2D object without stalling on the copy
+    ///
+    /// `region` is given as (x, y, width, height).
+    pub fn enqueue(
+        &mut self,
+        texture: &mut Texture2D,
+        region: (u32, u32, u32, u32),
+        data: &[u8],
+    ) -> Result<UploadId, Texture2DError> {
+        let (x, y, width, height) = region;
+        texture.check_region(0, x, y, width, height, data.len())?;
+
+        // wait for the previous upload from this buffer to finish
+        let slot = &mut self.slots[self.next_slot];
+        if let Some((_, fence)) = slot.pending.take() {
+            if fence.wait_forever() == SyncStatus::Failed {
+                return Err(Texture2DError::UploadFailed);
+            }
+        }
+
+        let id = UploadId(self.next_id);
+        unsafe {
+            // grow the buffer if needed, the previous upload from it is done
+            if data.len() > slot.buffer.capacity() {
+                let grown = slot.buffer.init_null(
+                    gl::PIXEL_UNPACK_BUFFER,
+                    data.len(),
+                    BufferUsage::StreamDraw,
+                );
+                if grown.is_err() {
+                    gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, 0);
+                    return Err(Texture2DError::UploadFailed);
+                }
+            }
+
+            // copy the data into the buffer, the GL isn't reading from it anymore
+            let access = MapAccess::write()
+                .with_invalidate_range()
+                .with_unsynchronized();
+            let copied = slot
+                .buffer
+                .map_range(0..data.len(), access)
+                .and_then(|mut mapped| {
+                    mapped.as_mut_slice()?.copy_from_slice(data);
+                    mapped.unmap()
+                });
+            if copied.is_err() {
+                gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, 0);
+                return Err(Texture2DError::UploadFailed);
+            }
+
+            // the data pointer is an offset into the bound unpack buffer
+            gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, slot.buffer.resource.get_raw());
+            gl::BindTexture(gl::TEXTURE_2D, texture.resource.get_raw());
+            let _unpack = PixelStore::unpack();
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                x as _,
+                y as _,
+                width as _,
+                height as _,
+                texture.format.pixel_format(),
+                texture.format.component_type(),
+                ptr::null(),
+            );
+            gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, 0);
+        }
+        slot.pending = Some((id, GLSyncResource::new()));
+
+        self.next_slot = (self.next_slot + 1) % self.slots.len();
+        self.next_id += 1;
+
+        Ok(id)
+    }
+
+    /// Checks if an upload has finished without blocking
+    pub fn is_complete(&self, id: UploadId) -> bool {
+        // uploads that no longer own a buffer were waited on before it was reused
+        id.0 < self.next_id
+            && self.slots.iter().all(|slot| match slot.pending {
+                Some((pending_id, ref fence)) if pending_id == id => {
+                    fence.wait(0) == SyncStatus::Signaled
+                }
+                _ => true,
+            })
+    }
+
+    /// Blocks until every enqueued upload has finished
+    pub fn finish(&mut self) -> Result<(), Texture2DError> {
+        let mut result = Ok(());
+        for slot in self.slots.iter_mut() {
+            if let Some((_, fence)) = slot.pending.take() {
+                if fence.wait_forever() == SyncStatus::Failed {
+                    result = Err(Texture2DError::UploadFailed);
+                }
+            }
+        }
+        result
+    }
+}